@@ -0,0 +1,1549 @@
+//! AT command responses and notifications of the nRF9160 modem, parsed into typed values.  Each
+//! `parse` takes the full response text, so an error response, a missing or extra field, a value
+//! out of range or a line cut short is an `Error` instead of a wrong value.  Sending the commands
+//! is left to the firmware.
+use core::fmt::write;
+use core::str::FromStr;
+use heapless::{String, Vec};
+use serde::Serialize;
+
+/// ICCIDs are at most 20 digits
+pub const ICCID_LEN: usize = 20;
+/// IMEIs are 15 digits
+pub const IMEI_LEN: usize = 15;
+/// Most neighbor cells kept from a %NCELLMEAS report
+pub const MAX_NEIGHBORS: usize = 8;
+/// Longest %NCELLMEAS notification, every field at its widest with the 17 neighbor cells the
+/// modem reports at most
+pub const NCELLMEAS_MAX_LEN: usize = 640;
+/// Longest credential that fits in the AT%CMNG write command
+pub const MAX_CREDENTIAL_LEN: usize = 96;
+/// Value of the signal quality fields when unknown or not detectable
+const UNKNOWN: i32 = 255;
+/// Value of the %XSNRSQ SNR when unknown
+const XSNRSQ_UNKNOWN: i32 = 127;
+/// Timing advance of %NCELLMEAS when not known
+const TIMING_ADVANCE_UNKNOWN: i32 = 65535;
+
+/// AT response errors
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Error {
+    /// The modem answered ERROR
+    Error,
+    /// The modem answered +CME ERROR with the error code
+    CmeError(u16),
+    /// No line with the expected prefix
+    MissingResponse,
+    /// The line has no line ending, it did not fit the buffer
+    Truncated,
+    /// A required field is missing or empty
+    MissingParameter,
+    /// More fields than the response has
+    ExtraParameter,
+    /// A field is malformed, out of range or does not fit its buffer
+    InvalidParameter,
+}
+
+/// Find the line starting with `prefix` and return its fields.  Fails with the modem's error
+/// when an ERROR or +CME ERROR line comes first
+pub fn parameters<'a>(response: &'a str, prefix: &str) -> Result<Parameters<'a>, Error> {
+    let mut rest = response;
+    loop {
+        let (line, terminated) = match rest.find("\r\n") {
+            Some(end) => {
+                let line = &rest[..end];
+                rest = &rest[end + 2..];
+                (line, true)
+            }
+            None => (rest, false),
+        };
+        if let Some(fields) = line.strip_prefix(prefix) {
+            if !terminated {
+                return Err(Error::Truncated);
+            }
+            return Ok(Parameters::new(fields));
+        }
+        if line == "ERROR" {
+            return Err(Error::Error);
+        }
+        if let Some(code) = line.strip_prefix("+CME ERROR:") {
+            let code = code.trim().parse().map_err(|_| Error::InvalidParameter)?;
+            return Err(Error::CmeError(code));
+        }
+        if !terminated {
+            return Err(Error::MissingResponse);
+        }
+    }
+}
+
+/// Comma separated fields of a response line.  Strings are quoted and may hold commas, an empty
+/// field is a missing value
+pub struct Parameters<'a> {
+    rest: Option<&'a str>,
+}
+
+/// A field, whether it was quoted and its text without the quotes
+type Field<'a> = (bool, &'a str);
+
+impl<'a> Parameters<'a> {
+    fn new(fields: &'a str) -> Self {
+        let fields = fields.trim_start();
+        Parameters {
+            rest: (!fields.is_empty()).then_some(fields),
+        }
+    }
+
+    fn next(&mut self) -> Result<Option<Field<'a>>, Error> {
+        let rest = match self.rest {
+            Some(rest) => rest,
+            None => return Ok(None),
+        };
+        let (field, tail) = match rest.strip_prefix('"') {
+            Some(quoted) => {
+                let end = quoted.find('"').ok_or(Error::InvalidParameter)?;
+                let tail = &quoted[end + 1..];
+                if !(tail.is_empty() || tail.starts_with(',')) {
+                    return Err(Error::InvalidParameter);
+                }
+                ((true, &quoted[..end]), tail)
+            }
+            None => {
+                let end = rest.find(',').unwrap_or(rest.len());
+                ((false, rest[..end].trim()), &rest[end..])
+            }
+        };
+        self.rest = tail.strip_prefix(',');
+        Ok(Some(field))
+    }
+
+    /// Next field when present and not empty
+    fn value(&mut self) -> Result<Option<Field<'a>>, Error> {
+        Ok(self.next()?.filter(|(_, text)| !text.is_empty()))
+    }
+
+    /// Integer field, a quoted or too large value is invalid
+    pub fn int<T: FromStr>(&mut self) -> Result<T, Error> {
+        self.optional_int()?.ok_or(Error::MissingParameter)
+    }
+
+    /// Integer field that may be empty or left out
+    pub fn optional_int<T: FromStr>(&mut self) -> Result<Option<T>, Error> {
+        match self.value()? {
+            Some((false, text)) => text.parse().map(Some).map_err(|_| Error::InvalidParameter),
+            Some((true, _)) => Err(Error::InvalidParameter),
+            None => Ok(None),
+        }
+    }
+
+    /// Quoted string field
+    pub fn string(&mut self) -> Result<&'a str, Error> {
+        self.optional_string()?.ok_or(Error::MissingParameter)
+    }
+
+    /// Quoted string field that may be empty or left out
+    pub fn optional_string(&mut self) -> Result<Option<&'a str>, Error> {
+        match self.value()? {
+            Some((true, text)) => Ok(Some(text)),
+            Some((false, _)) => Err(Error::InvalidParameter),
+            None => Ok(None),
+        }
+    }
+
+    /// Unquoted field that is not a number, e.g. the ICCID
+    pub fn raw(&mut self) -> Result<&'a str, Error> {
+        match self.value()? {
+            Some((false, text)) => Ok(text),
+            Some((true, _)) => Err(Error::InvalidParameter),
+            None => Err(Error::MissingParameter),
+        }
+    }
+
+    /// Number of fields left
+    pub fn remaining(&self) -> usize {
+        self.rest.map_or(0, |rest| {
+            let mut quoted = false;
+            1 + rest
+                .chars()
+                .filter(|c| {
+                    if *c == '"' {
+                        quoted = !quoted;
+                    }
+                    *c == ',' && !quoted
+                })
+                .count()
+        })
+    }
+
+    /// All fields were read
+    pub fn finish(self) -> Result<(), Error> {
+        match self.rest {
+            Some(_) => Err(Error::ExtraParameter),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Copy a field into a heapless string, too long is invalid
+fn string<const N: usize>(s: &str) -> Result<String<N>, Error> {
+    let mut out = String::new();
+    out.push_str(s).map_err(|_| Error::InvalidParameter)?;
+    Ok(out)
+}
+
+/// Hexadecimal field of exactly `N` digits, the tracking area code and cell ID
+fn hex<const N: usize>(s: &str) -> Result<String<N>, Error> {
+    if s.len() != N || !s.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(Error::InvalidParameter);
+    }
+    string(s)
+}
+
+/// Mobile country and network code, 5 or 6 digits
+fn plmn(s: &str) -> Result<String<6>, Error> {
+    if !(5..=6).contains(&s.len()) || !s.chars().all(|c| c.is_ascii_digit()) {
+        return Err(Error::InvalidParameter);
+    }
+    string(s)
+}
+
+/// Bit string field of `len` bits, e.g. a GPRS timer or an eDRX value
+fn bits(s: Option<&str>, len: usize) -> Result<Option<u8>, Error> {
+    s.map(|s| {
+        if s.len() != len {
+            return Err(Error::InvalidParameter);
+        }
+        u8::from_str_radix(s, 2).map_err(|_| Error::InvalidParameter)
+    })
+    .transpose()
+}
+
+/// Check a value is within `range` or the `unknown` value
+fn check<T: PartialOrd>(
+    value: T,
+    range: core::ops::RangeInclusive<T>,
+    unknown: T,
+) -> Result<T, Error> {
+    if range.contains(&value) || value == unknown {
+        Ok(value)
+    } else {
+        Err(Error::InvalidParameter)
+    }
+}
+
+/// RSRP (dBm) from the raw value, -140 dBm + value, `None` when unknown
+fn rsrp_dbm(rsrp: i32) -> Option<i32> {
+    (rsrp != UNKNOWN).then(|| rsrp - 140)
+}
+
+/// RSRQ (dB) from the raw value, -20 dB + value / 2, `None` when unknown
+fn rsrq_db(rsrq: i32) -> Option<f32> {
+    (rsrq != UNKNOWN).then(|| (rsrq as f32 - 40.0) / 2.0)
+}
+
+/// Raw RSRP of the nRF91 reports, 3GPP range with the extended values below -140 dBm
+fn nrf_rsrp(params: &mut Parameters) -> Result<i32, Error> {
+    check(params.int()?, -17..=97, UNKNOWN)
+}
+
+/// Raw RSRQ of the nRF91 reports, 3GPP range with the extended values at both ends
+fn nrf_rsrq(params: &mut Parameters) -> Result<i32, Error> {
+    check(params.int()?, -30..=46, UNKNOWN)
+}
+
+/// Extended signal quality, AT+CESQ.  Fields hold the raw values, 99 or 255 is unknown
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Cesq {
+    pub rxlev: u8,
+    pub ber: u8,
+    pub rscp: u8,
+    pub ecno: u8,
+    pub rsrq: u8,
+    pub rsrp: u8,
+}
+
+impl Cesq {
+    pub fn parse(response: &str) -> Result<Self, Error> {
+        let mut params = parameters(response, "+CESQ:")?;
+        let cesq = Cesq {
+            rxlev: check(params.int()?, 0..=63, 99)?,
+            ber: check(params.int()?, 0..=7, 99)?,
+            rscp: check(params.int()?, 0..=96, 255)?,
+            ecno: check(params.int()?, 0..=49, 255)?,
+            rsrq: check(params.int()?, 0..=34, 255)?,
+            rsrp: check(params.int()?, 0..=97, 255)?,
+        };
+        params.finish()?;
+        Ok(cesq)
+    }
+
+    /// Reference signal received power (dBm)
+    pub fn rsrp_dbm(&self) -> Option<i32> {
+        rsrp_dbm(self.rsrp as i32)
+    }
+
+    /// Reference signal received quality (dB)
+    pub fn rsrq_db(&self) -> Option<f32> {
+        rsrq_db(self.rsrq as i32)
+    }
+}
+
+/// Parse the AT%XSNRSQ? response and return the signal to noise ratio in dB, `None` when
+/// unknown.  SNR = value - 24 dB
+pub fn parse_snr(response: &str) -> Result<Option<i32>, Error> {
+    let mut params = parameters(response, "%XSNRSQ:")?;
+    let snr = check(params.int()?, 0..=49, XSNRSQ_UNKNOWN)?;
+    // Cell selection RX level and coverage enhancement level
+    params.int::<i32>()?;
+    params.int::<i32>()?;
+    params.finish()?;
+    Ok((snr != XSNRSQ_UNKNOWN).then(|| snr - 24))
+}
+
+/// Parse the AT%XTEMP? response and return the modem temperature in °C
+pub fn parse_temperature(response: &str) -> Result<i32, Error> {
+    let mut params = parameters(response, "%XTEMP:")?;
+    let temperature = params.int()?;
+    params.finish()?;
+    if !(-40..=125).contains(&temperature) {
+        return Err(Error::InvalidParameter);
+    }
+    Ok(temperature)
+}
+
+/// EPS network registration status of +CEREG and %XMONITOR
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[serde(rename_all = "snake_case")]
+pub enum RegistrationStatus {
+    /// Not registered and not searching
+    NotSearching,
+    /// Registered to the home network
+    Home,
+    /// Not registered, searching for a network
+    Searching,
+    /// Registration denied
+    Denied,
+    /// Unknown, e.g. out of coverage
+    Unknown,
+    /// Registered, roaming
+    Roaming,
+    /// UICC failure
+    SimFailure,
+}
+
+impl RegistrationStatus {
+    fn from_stat(stat: i32) -> Result<Self, Error> {
+        match stat {
+            0 => Ok(RegistrationStatus::NotSearching),
+            1 => Ok(RegistrationStatus::Home),
+            2 => Ok(RegistrationStatus::Searching),
+            3 => Ok(RegistrationStatus::Denied),
+            4 => Ok(RegistrationStatus::Unknown),
+            5 => Ok(RegistrationStatus::Roaming),
+            90 => Ok(RegistrationStatus::SimFailure),
+            _ => Err(Error::InvalidParameter),
+        }
+    }
+
+    /// Registered to the network, either home or roaming
+    pub fn is_registered(&self) -> bool {
+        matches!(self, RegistrationStatus::Home | RegistrationStatus::Roaming)
+    }
+}
+
+/// Access technology of +CEREG and %XMONITOR, 7 LTE-M or 9 NB-IoT
+fn act(value: Option<i32>) -> Result<Option<i32>, Error> {
+    match value {
+        Some(7 | 9) | None => Ok(value),
+        Some(_) => Err(Error::InvalidParameter),
+    }
+}
+
+/// Network registration status, AT+CEREG? response or +CEREG notification.  The location and
+/// PSM fields are only sent with +CEREG=5 while registered
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cereg {
+    pub stat: RegistrationStatus,
+    /// Tracking area code, hexadecimal
+    pub tac: Option<String<4>>,
+    /// E-UTRAN cell ID, hexadecimal
+    pub cell_id: Option<String<8>>,
+    /// Access technology, 7 LTE-M, 9 NB-IoT
+    pub act: Option<i32>,
+    /// Granted PSM active time (T3324) and periodic TAU (T3412 extended), GPRS timer values
+    pub active_time: Option<u8>,
+    pub periodic_tau: Option<u8>,
+}
+
+impl Cereg {
+    /// Parse the AT+CEREG? response, which starts with the notification setting
+    pub fn parse(response: &str) -> Result<Self, Error> {
+        let mut params = parameters(response, "+CEREG:")?;
+        check(params.int()?, 0..=5, 0)?;
+        Self::fields(params)
+    }
+
+    /// Parse a +CEREG notification
+    pub fn parse_notification(notification: &str) -> Result<Self, Error> {
+        Self::fields(parameters(notification, "+CEREG:")?)
+    }
+
+    fn fields(mut params: Parameters) -> Result<Self, Error> {
+        let stat = RegistrationStatus::from_stat(params.int()?)?;
+        let tac = params.optional_string()?.map(hex).transpose()?;
+        let cell_id = params.optional_string()?.map(hex).transpose()?;
+        let act = act(params.optional_int()?)?;
+        // Cause type and reject cause
+        params.optional_int::<i32>()?;
+        params.optional_int::<i32>()?;
+        let active_time = bits(params.optional_string()?, 8)?;
+        let periodic_tau = bits(params.optional_string()?, 8)?;
+        params.finish()?;
+        Ok(Cereg {
+            stat,
+            tac,
+            cell_id,
+            act,
+            active_time,
+            periodic_tau,
+        })
+    }
+}
+
+/// Parse the AT+CGSN=1 response and return the IMEI
+pub fn parse_imei(response: &str) -> Result<String<IMEI_LEN>, Error> {
+    let mut params = parameters(response, "+CGSN:")?;
+    let imei = params.string()?;
+    params.finish()?;
+    if imei.len() != IMEI_LEN || !imei.chars().all(|c| c.is_ascii_digit()) {
+        return Err(Error::InvalidParameter);
+    }
+    string(imei)
+}
+
+/// Parse the AT%XICCID response and return the ICCID of the active SIM.  The ICCID is sent
+/// unquoted, padded with F to an even length
+pub fn parse_iccid(response: &str) -> Result<String<ICCID_LEN>, Error> {
+    let mut params = parameters(response, "%XICCID:")?;
+    let iccid = params.raw()?.trim_end_matches(['F', 'f']);
+    params.finish()?;
+    if !(18..=ICCID_LEN).contains(&iccid.len()) || !iccid.chars().all(|c| c.is_ascii_digit()) {
+        return Err(Error::InvalidParameter);
+    }
+    string(iccid)
+}
+
+/// Network time, AT+CCLK?.  Only known once the network sent it after registration
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct NetworkTime {
+    pub year: u16,
+    pub month: u8,
+    pub day: u8,
+    pub hour: u8,
+    pub minute: u8,
+    pub second: u8,
+    /// Offset from UTC in quarter hours
+    pub timezone: i8,
+}
+
+impl NetworkTime {
+    /// Parse the AT+CCLK? response, time is sent as "yy/MM/dd,hh:mm:ss±zz"
+    pub fn parse(response: &str) -> Result<Self, Error> {
+        let mut params = parameters(response, "+CCLK:")?;
+        let time = params.string()?;
+        params.finish()?;
+
+        let bytes = time.as_bytes();
+        if bytes.len() != 20
+            || !time.is_ascii()
+            || [(2, b'/'), (5, b'/'), (8, b','), (11, b':'), (14, b':')]
+                .iter()
+                .any(|(i, c)| bytes[*i] != *c)
+            || !matches!(bytes[17], b'+' | b'-')
+        {
+            return Err(Error::InvalidParameter);
+        }
+        let field = |start: usize, range: core::ops::RangeInclusive<u8>| {
+            time[start..start + 2]
+                .parse::<u8>()
+                .ok()
+                .filter(|value| range.contains(value))
+                .ok_or(Error::InvalidParameter)
+        };
+        let timezone = time[17..]
+            .parse::<i8>()
+            .ok()
+            .filter(|tz| (-48..=56).contains(tz))
+            .ok_or(Error::InvalidParameter)?;
+        Ok(NetworkTime {
+            year: 2000 + field(0, 0..=99)? as u16,
+            month: field(3, 1..=12)?,
+            day: field(6, 1..=31)?,
+            hour: field(9, 0..=23)?,
+            minute: field(12, 0..=59)?,
+            second: field(15, 0..=59)?,
+            timezone,
+        })
+    }
+}
+
+/// Modem and serving cell status, AT%XMONITOR.  Only the registration status is sent while not
+/// registered
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct XMonitor {
+    pub stat: RegistrationStatus,
+    /// Mobile country and network code of the operator
+    pub plmn: Option<String<6>>,
+    /// Tracking area code, hexadecimal
+    pub tac: Option<String<4>>,
+    /// Access technology, 7 LTE-M, 9 NB-IoT
+    pub act: Option<i32>,
+    pub band: Option<i32>,
+    /// E-UTRAN cell ID, hexadecimal
+    pub cell_id: Option<String<8>>,
+    pub phys_cell_id: Option<i32>,
+    pub earfcn: Option<i32>,
+    /// RSRP and SNR as raw values, 255 is unknown
+    pub rsrp: Option<i32>,
+    pub snr: Option<i32>,
+}
+
+impl XMonitor {
+    /// Parse the AT%XMONITOR response.  The PSM and eDRX fields after the SNR are not used, they
+    /// are read with AT+CEREG? and AT+CEDRXRDP
+    pub fn parse(response: &str) -> Result<Self, Error> {
+        let mut params = parameters(response, "%XMONITOR:")?;
+        let stat = RegistrationStatus::from_stat(params.int()?)?;
+        // Full and short operator names
+        params.optional_string()?;
+        params.optional_string()?;
+        let monitor = XMonitor {
+            stat,
+            plmn: params.optional_string()?.map(plmn).transpose()?,
+            tac: params.optional_string()?.map(hex).transpose()?,
+            act: act(params.optional_int()?)?,
+            band: params
+                .optional_int()?
+                .map(|band| check(band, 1..=88, 0))
+                .transpose()?,
+            cell_id: params.optional_string()?.map(hex).transpose()?,
+            phys_cell_id: params
+                .optional_int()?
+                .map(|pci| check(pci, 0..=503, 0))
+                .transpose()?,
+            earfcn: params
+                .optional_int()?
+                .map(|earfcn| check(earfcn, 0..=262143, 0))
+                .transpose()?,
+            rsrp: params
+                .optional_int()?
+                .map(|rsrp| check(rsrp, -17..=97, UNKNOWN))
+                .transpose()?,
+            snr: params
+                .optional_int()?
+                .map(|snr| check(snr, 0..=49, XSNRSQ_UNKNOWN))
+                .transpose()?,
+        };
+        // NW provided eDRX value, active time, periodic TAU and periodic TAU extended
+        if params.remaining() > 4 {
+            return Err(Error::ExtraParameter);
+        }
+        Ok(monitor)
+    }
+}
+
+/// PSM setting, AT+CPSMS?.  Timers are GPRS timer values
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Cpsms {
+    pub enabled: bool,
+    pub periodic_tau: Option<u8>,
+    pub active_time: Option<u8>,
+}
+
+impl Cpsms {
+    pub fn parse(response: &str) -> Result<Self, Error> {
+        let mut params = parameters(response, "+CPSMS:")?;
+        let mode = check(params.int()?, 0..=1, 0)?;
+        // Legacy GERAN/UTRAN timers
+        params.optional_string()?;
+        params.optional_string()?;
+        let cpsms = Cpsms {
+            enabled: mode == 1,
+            periodic_tau: bits(params.optional_string()?, 8)?,
+            active_time: bits(params.optional_string()?, 8)?,
+        };
+        params.finish()?;
+        Ok(cpsms)
+    }
+
+    /// AT+CPSMS command requesting PSM with the periodic TAU and active time GPRS timer values,
+    /// or disabling it with `None`
+    pub fn command(timers: Option<(u8, u8)>) -> String<48> {
+        let mut cmd = String::new();
+        match timers {
+            Some((periodic_tau, active_time)) => write(
+                &mut cmd,
+                format_args!(
+                    r#"AT+CPSMS=1,,,"{:08b}","{:08b}""#,
+                    periodic_tau, active_time
+                ),
+            )
+            .unwrap(),
+            None => cmd.push_str("AT+CPSMS=0").unwrap(),
+        }
+        cmd
+    }
+}
+
+/// GPRS timer of the PSM settings, the value is a 3 bit unit and a 5 bit multiplier
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum GprsTimer {
+    /// Periodic TAU (T3412 extended), GPRS timer 3
+    PeriodicTau,
+    /// Active time (T3324), GPRS timer 2
+    ActiveTime,
+}
+
+impl GprsTimer {
+    /// Unit bits of a deactivated timer
+    const DEACTIVATED: u8 = 0b111;
+
+    /// Units as (unit bits, seconds), shortest first
+    fn units(&self) -> &'static [(u8, u32)] {
+        match self {
+            GprsTimer::PeriodicTau => &[
+                (0b011, 2),
+                (0b100, 30),
+                (0b101, 60),
+                (0b000, 600),
+                (0b001, 3600),
+                (0b010, 36000),
+                (0b110, 1_152_000),
+            ],
+            GprsTimer::ActiveTime => &[(0b000, 2), (0b001, 60), (0b010, 360)],
+        }
+    }
+
+    /// Encode seconds into a timer value, rounded up to the next value the units can hold
+    pub fn encode(&self, secs: u32) -> u8 {
+        let units = self.units();
+        for (unit, step) in units {
+            let value = secs.saturating_add(step - 1) / step;
+            if value <= 0b11111 {
+                return unit << 5 | value as u8;
+            }
+        }
+        let (unit, _) = units[units.len() - 1];
+        unit << 5 | 0b11111
+    }
+
+    /// Decode a timer value into seconds, `None` when deactivated
+    pub fn decode(&self, value: u8) -> Option<u32> {
+        let unit = value >> 5;
+        if unit == Self::DEACTIVATED {
+            return None;
+        }
+        let (_, step) = self.units().iter().find(|(u, _)| *u == unit)?;
+        Some((value & 0b11111) as u32 * step)
+    }
+}
+
+/// Modem functional mode, AT+CFUN
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum FunctionalMode {
+    /// Power off, settings are stored
+    PowerOff = 0,
+    /// Normal mode
+    Normal = 1,
+    /// Offline, SIM still accessible
+    Offline = 4,
+    /// LTE deactivated
+    LteOff = 20,
+    /// LTE activated
+    LteOn = 21,
+}
+
+impl FunctionalMode {
+    fn from_value(value: i32) -> Result<Self, Error> {
+        match value {
+            0 => Ok(FunctionalMode::PowerOff),
+            1 => Ok(FunctionalMode::Normal),
+            4 => Ok(FunctionalMode::Offline),
+            20 => Ok(FunctionalMode::LteOff),
+            21 => Ok(FunctionalMode::LteOn),
+            _ => Err(Error::InvalidParameter),
+        }
+    }
+
+    /// Parse the AT+CFUN? response
+    pub fn parse(response: &str) -> Result<Self, Error> {
+        let mut params = parameters(response, "+CFUN:")?;
+        let mode = Self::from_value(params.int()?)?;
+        params.finish()?;
+        Ok(mode)
+    }
+
+    /// AT+CFUN command setting this mode
+    pub fn command(&self) -> String<16> {
+        let mut cmd = String::new();
+        write(&mut cmd, format_args!("AT+CFUN={}", *self as u8)).unwrap();
+        cmd
+    }
+}
+
+/// Credential types of the modem's credential storage, AT%CMNG
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum CredentialType {
+    RootCert = 0,
+    ClientCert = 1,
+    ClientPrivateKey = 2,
+    Psk = 3,
+    PskId = 4,
+}
+
+impl CredentialType {
+    fn from_value(value: i32) -> Result<Self, Error> {
+        match value {
+            0 => Ok(CredentialType::RootCert),
+            1 => Ok(CredentialType::ClientCert),
+            2 => Ok(CredentialType::ClientPrivateKey),
+            3 => Ok(CredentialType::Psk),
+            4 => Ok(CredentialType::PskId),
+            _ => Err(Error::InvalidParameter),
+        }
+    }
+}
+
+/// Credential of the AT%CMNG=1 list
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Cmng {
+    pub tag: u32,
+    pub ty: CredentialType,
+}
+
+impl Cmng {
+    /// Parse the AT%CMNG=1 response for a single tag and type, `None` when the credential is not
+    /// stored.  The SHA-256 of the content is sent as well
+    pub fn parse(response: &str) -> Result<Option<Self>, Error> {
+        let mut params = match parameters(response, "%CMNG:") {
+            Ok(params) => params,
+            Err(Error::MissingResponse) => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        let credential = Cmng {
+            tag: params.int()?,
+            ty: CredentialType::from_value(params.int()?)?,
+        };
+        if let Some(sha) = params.optional_string()? {
+            if sha.len() != 64 || !sha.chars().all(|c| c.is_ascii_hexdigit()) {
+                return Err(Error::InvalidParameter);
+            }
+        }
+        params.finish()?;
+        Ok(Some(credential))
+    }
+
+    /// AT%CMNG command listing the credential of `tag` and `ty`
+    pub fn list_command(tag: u32, ty: CredentialType) -> String<32> {
+        let mut cmd = String::new();
+        write(&mut cmd, format_args!("AT%CMNG=1,{},{}", tag, ty as u32)).unwrap();
+        cmd
+    }
+
+    /// AT%CMNG command deleting a key or certificate
+    pub fn delete_command(tag: u32, ty: CredentialType) -> String<32> {
+        let mut cmd = String::new();
+        write(&mut cmd, format_args!("AT%CMNG=3,{},{}", tag, ty as u32)).unwrap();
+        cmd
+    }
+
+    /// AT%CMNG command writing a key or certificate.  `data` is sent quoted, so it may not hold
+    /// a quote, and must be at most `MAX_CREDENTIAL_LEN` bytes
+    pub fn write_command(tag: u32, ty: CredentialType, data: &str) -> Result<String<128>, Error> {
+        if data.is_empty() || data.len() > MAX_CREDENTIAL_LEN || data.contains('"') {
+            return Err(Error::InvalidParameter);
+        }
+        let mut cmd = String::new();
+        write(
+            &mut cmd,
+            format_args!(r#"AT%CMNG=0,{},{},"{}""#, tag, ty as u32, data),
+        )
+        .map_err(|_| Error::InvalidParameter)?;
+        Ok(cmd)
+    }
+}
+
+/// Serving cell of a %NCELLMEAS report
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ServingCell {
+    /// E-UTRAN cell ID, hexadecimal
+    pub cell_id: String<8>,
+    /// Mobile country and network code of the operator
+    pub plmn: String<6>,
+    /// Tracking area code, hexadecimal
+    pub tac: String<4>,
+    /// Timing advance, `None` when not known
+    pub timing_advance: Option<i32>,
+    pub earfcn: i32,
+    pub phys_cell_id: i32,
+    pub rsrp: Option<i32>,
+    pub rsrq: Option<f32>,
+}
+
+/// Neighbor cell of a %NCELLMEAS report
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct NeighborCell {
+    pub earfcn: i32,
+    pub phys_cell_id: i32,
+    pub rsrp: Option<i32>,
+    pub rsrq: Option<f32>,
+}
+
+/// Result of a neighbor cell measurement, the %NCELLMEAS notification
+#[derive(Debug, Clone, PartialEq)]
+pub struct Ncellmeas {
+    pub serving: ServingCell,
+    pub neighbors: Vec<NeighborCell, MAX_NEIGHBORS>,
+}
+
+impl Ncellmeas {
+    /// Parse the %NCELLMEAS notification, `None` when the measurement failed or was
+    /// interrupted.  Neighbors past `MAX_NEIGHBORS` are dropped
+    pub fn parse(notification: &str) -> Result<Option<Self>, Error> {
+        let mut params = parameters(notification, "%NCELLMEAS:")?;
+        // Status 1 is a failed and 2 an interrupted measurement
+        if check(params.int()?, 0..=2, 0)? != 0 {
+            return Ok(None);
+        }
+        let serving = ServingCell {
+            cell_id: hex(params.string()?)?,
+            plmn: plmn(params.string()?)?,
+            tac: hex(params.string()?)?,
+            timing_advance: {
+                let ta = check(params.int()?, 0..=20512, TIMING_ADVANCE_UNKNOWN)?;
+                (ta != TIMING_ADVANCE_UNKNOWN).then_some(ta)
+            },
+            earfcn: check(params.int()?, 0..=262143, 0)?,
+            phys_cell_id: check(params.int()?, 0..=503, 0)?,
+            rsrp: rsrp_dbm(nrf_rsrp(&mut params)?),
+            rsrq: rsrq_db(nrf_rsrq(&mut params)?),
+        };
+        // Measurement time
+        params.int::<u64>()?;
+
+        // Neighbors are 5 fields each, followed by the timing advance measurement time
+        let remaining = params.remaining();
+        if remaining == 0 || (remaining - 1) % 5 != 0 {
+            return Err(Error::MissingParameter);
+        }
+        let mut neighbors = Vec::new();
+        for _ in 0..(remaining - 1) / 5 {
+            let cell = NeighborCell {
+                earfcn: check(params.int()?, 0..=262143, 0)?,
+                phys_cell_id: check(params.int()?, 0..=503, 0)?,
+                rsrp: rsrp_dbm(nrf_rsrp(&mut params)?),
+                rsrq: rsrq_db(nrf_rsrq(&mut params)?),
+            };
+            // Time difference to the serving cell measurement
+            params.int::<i64>()?;
+            // Full once MAX_NEIGHBORS are kept
+            let _ = neighbors.push(cell);
+        }
+        params.int::<u64>()?;
+        params.finish()?;
+        Ok(Some(Ncellmeas { serving, neighbors }))
+    }
+}
+
+/// eDRX report, the +CEDRXP notification or the AT+CEDRXRDP response
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Edrx {
+    /// Access technology, 0 when eDRX is not in use, 4 LTE-M, 5 NB-IoT
+    pub act: i32,
+    /// Network provided eDRX value and paging time window
+    pub edrx: Option<u8>,
+    pub ptw: Option<u8>,
+}
+
+impl Edrx {
+    /// Parse the AT+CEDRXRDP response
+    pub fn parse(response: &str) -> Result<Self, Error> {
+        Self::fields(parameters(response, "+CEDRXRDP:")?)
+    }
+
+    /// Parse a +CEDRXP notification
+    pub fn parse_notification(notification: &str) -> Result<Self, Error> {
+        Self::fields(parameters(notification, "+CEDRXP:")?)
+    }
+
+    fn fields(mut params: Parameters) -> Result<Self, Error> {
+        let act = check(params.int()?, 4..=5, 0)?;
+        // Requested eDRX value
+        params.optional_string()?;
+        // Values are sent as 4 character bit strings
+        let edrx = Edrx {
+            act,
+            edrx: bits(params.optional_string()?, 4)?,
+            ptw: bits(params.optional_string()?, 4)?,
+        };
+        params.finish()?;
+        Ok(edrx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn error_responses() {
+        assert_eq!(Cesq::parse("ERROR\r\n"), Err(Error::Error));
+        assert_eq!(
+            Cesq::parse("+CME ERROR: 516\r\n"),
+            Err(Error::CmeError(516))
+        );
+        assert_eq!(
+            Cereg::parse("+CME ERROR: x\r\n"),
+            Err(Error::InvalidParameter)
+        );
+        assert_eq!(Cesq::parse("OK\r\n"), Err(Error::MissingResponse));
+        assert_eq!(Cesq::parse(""), Err(Error::MissingResponse));
+    }
+
+    #[test]
+    fn quoted_fields_may_hold_commas() {
+        let mut params = parameters("+X: \"a,b\",,1\r\n", "+X:").unwrap();
+        assert_eq!(params.remaining(), 3);
+        assert_eq!(params.string(), Ok("a,b"));
+        assert_eq!(params.optional_int::<i32>(), Ok(None));
+        assert_eq!(params.int::<i32>(), Ok(1));
+        assert_eq!(params.finish(), Ok(()));
+    }
+
+    #[test]
+    fn unterminated_quote() {
+        let mut params = parameters("+X: \"abc\r\n", "+X:").unwrap();
+        assert_eq!(params.string(), Err(Error::InvalidParameter));
+    }
+
+    #[test]
+    fn cesq() {
+        let cesq = Cesq::parse("+CESQ: 99,99,255,255,31,62\r\nOK\r\n").unwrap();
+        assert_eq!(cesq.rsrp, 62);
+        assert_eq!(cesq.rsrp_dbm(), Some(-78));
+        assert_eq!(cesq.rsrq_db(), Some(-4.5));
+
+        let unknown = Cesq::parse("+CESQ: 99,99,255,255,255,255\r\nOK\r\n").unwrap();
+        assert_eq!(unknown.rsrp_dbm(), None);
+        assert_eq!(unknown.rsrq_db(), None);
+    }
+
+    #[test]
+    fn cesq_bad_fields() {
+        assert_eq!(
+            Cesq::parse("+CESQ: 99,99,255,255,31\r\nOK\r\n"),
+            Err(Error::MissingParameter)
+        );
+        assert_eq!(
+            Cesq::parse("+CESQ: 99,99,255,255,31,62,1\r\nOK\r\n"),
+            Err(Error::ExtraParameter)
+        );
+        assert_eq!(
+            Cesq::parse("+CESQ: 99,99,255,255,31,98\r\nOK\r\n"),
+            Err(Error::InvalidParameter)
+        );
+        assert_eq!(
+            Cesq::parse("+CESQ: 99,99,255,255,31,300\r\nOK\r\n"),
+            Err(Error::InvalidParameter)
+        );
+        assert_eq!(
+            Cesq::parse("+CESQ: 99,99,255,255,\"31\",62\r\nOK\r\n"),
+            Err(Error::InvalidParameter)
+        );
+        assert_eq!(
+            Cesq::parse("+CESQ: 99,99,255,255,31,6"),
+            Err(Error::Truncated)
+        );
+    }
+
+    #[test]
+    fn snr_and_temperature() {
+        assert_eq!(parse_snr("%XSNRSQ: 39,53,0\r\nOK\r\n"), Ok(Some(15)));
+        assert_eq!(parse_snr("%XSNRSQ: 127,53,0\r\nOK\r\n"), Ok(None));
+        assert_eq!(
+            parse_snr("%XSNRSQ: 50,53,0\r\nOK\r\n"),
+            Err(Error::InvalidParameter)
+        );
+        assert_eq!(parse_temperature("%XTEMP: 37\r\nOK\r\n"), Ok(37));
+        assert_eq!(parse_temperature("%XTEMP: -5\r\nOK\r\n"), Ok(-5));
+        assert_eq!(
+            parse_temperature("%XTEMP: 37,1\r\nOK\r\n"),
+            Err(Error::ExtraParameter)
+        );
+        assert_eq!(parse_temperature("%XTEMP: 3"), Err(Error::Truncated));
+    }
+
+    #[test]
+    fn cereg_registered() {
+        let cereg = Cereg::parse(
+            "+CEREG: 5,1,\"0140\",\"0A0D8506\",7,,,\"00100001\",\"00101000\"\r\nOK\r\n",
+        )
+        .unwrap();
+        assert_eq!(cereg.stat, RegistrationStatus::Home);
+        assert!(cereg.stat.is_registered());
+        assert_eq!(cereg.tac.as_deref(), Some("0140"));
+        assert_eq!(cereg.cell_id.as_deref(), Some("0A0D8506"));
+        assert_eq!(cereg.act, Some(7));
+        assert_eq!(cereg.active_time, Some(0b0010_0001));
+        assert_eq!(cereg.periodic_tau, Some(0b0010_1000));
+    }
+
+    #[test]
+    fn cereg_searching() {
+        let cereg = Cereg::parse("+CEREG: 5,2\r\nOK\r\n").unwrap();
+        assert_eq!(cereg.stat, RegistrationStatus::Searching);
+        assert_eq!(cereg.tac, None);
+        assert_eq!(cereg.active_time, None);
+
+        let cereg = Cereg::parse("+CEREG: 5,4,\"\",\"\",7\r\nOK\r\n").unwrap();
+        assert_eq!(cereg.stat, RegistrationStatus::Unknown);
+        assert_eq!(cereg.cell_id, None);
+        assert_eq!(
+            Cereg::parse("+CEREG: 5,90\r\nOK\r\n").unwrap().stat,
+            RegistrationStatus::SimFailure
+        );
+    }
+
+    #[test]
+    fn cereg_notification() {
+        let cereg = Cereg::parse_notification(
+            "+CEREG: 5,\"002F\",\"0012BEEF\",9,,,\"11100000\",\"11100000\"\r\n",
+        )
+        .unwrap();
+        assert_eq!(cereg.stat, RegistrationStatus::Roaming);
+        assert_eq!(cereg.act, Some(9));
+        assert_eq!(cereg.active_time, Some(0b1110_0000));
+        assert_eq!(
+            Cereg::parse_notification("+CEREG: 3\r\n").unwrap().stat,
+            RegistrationStatus::Denied
+        );
+    }
+
+    #[test]
+    fn cereg_bad_fields() {
+        assert_eq!(
+            Cereg::parse("+CEREG: 5\r\nOK\r\n"),
+            Err(Error::MissingParameter)
+        );
+        assert_eq!(
+            Cereg::parse("+CEREG: 5,6\r\nOK\r\n"),
+            Err(Error::InvalidParameter)
+        );
+        assert_eq!(
+            Cereg::parse("+CEREG: 5,1,\"01400\",\"0A0D8506\",7\r\nOK\r\n"),
+            Err(Error::InvalidParameter)
+        );
+        assert_eq!(
+            Cereg::parse("+CEREG: 5,1,\"0140\",\"0A0D85G6\",7\r\nOK\r\n"),
+            Err(Error::InvalidParameter)
+        );
+        assert_eq!(
+            Cereg::parse("+CEREG: 5,1,\"0140\",\"0A0D8506\",8\r\nOK\r\n"),
+            Err(Error::InvalidParameter)
+        );
+        assert_eq!(
+            Cereg::parse(
+                "+CEREG: 5,1,\"0140\",\"0A0D8506\",7,,,\"0010001\",\"00101000\"\r\nOK\r\n"
+            ),
+            Err(Error::InvalidParameter)
+        );
+        assert_eq!(
+            Cereg::parse(
+                "+CEREG: 5,1,\"0140\",\"0A0D8506\",7,,,\"00100001\",\"00101000\",1\r\nOK\r\n"
+            ),
+            Err(Error::ExtraParameter)
+        );
+        assert_eq!(
+            Cereg::parse_notification("+CEREG: 1,\"0140\",\"0A0D"),
+            Err(Error::Truncated)
+        );
+    }
+
+    const XMONITOR: &str = "%XMONITOR: 1,\"EDAV\",\"EDAV\",\"26295\",\"00B7\",7,4,\"00011B07\",7,2300,63,39,\"\",\"11100000\",\"00000110\",\"01001001\"\r\nOK\r\n";
+
+    #[test]
+    fn xmonitor() {
+        let monitor = XMonitor::parse(XMONITOR).unwrap();
+        assert_eq!(monitor.stat, RegistrationStatus::Home);
+        assert_eq!(monitor.plmn.as_deref(), Some("26295"));
+        assert_eq!(monitor.tac.as_deref(), Some("00B7"));
+        assert_eq!(monitor.act, Some(7));
+        assert_eq!(monitor.band, Some(4));
+        assert_eq!(monitor.cell_id.as_deref(), Some("00011B07"));
+        assert_eq!(monitor.phys_cell_id, Some(7));
+        assert_eq!(monitor.earfcn, Some(2300));
+        assert_eq!(monitor.rsrp, Some(63));
+        assert_eq!(monitor.snr, Some(39));
+    }
+
+    #[test]
+    fn xmonitor_not_registered() {
+        let monitor = XMonitor::parse("%XMONITOR: 2\r\nOK\r\n").unwrap();
+        assert_eq!(monitor.stat, RegistrationStatus::Searching);
+        assert_eq!(monitor.plmn, None);
+        assert_eq!(monitor.cell_id, None);
+        assert_eq!(monitor.rsrp, None);
+    }
+
+    #[test]
+    fn xmonitor_bad_fields() {
+        assert_eq!(
+            XMonitor::parse("%XMONITOR: 1,\"EDAV\",\"EDAV\",\"2629\",\"00B7\",7\r\nOK\r\n"),
+            Err(Error::InvalidParameter)
+        );
+        assert_eq!(
+            XMonitor::parse(
+                "%XMONITOR: 1,\"EDAV\",\"EDAV\",\"26295\",\"00B7\",7,4,\"00011B07\",504\r\nOK\r\n"
+            ),
+            Err(Error::InvalidParameter)
+        );
+        assert_eq!(
+            XMonitor::parse("%XMONITOR: 1,\"EDAV\",\"EDAV\",\"26295\",\"00B7\",7,4,\"00011B07\",7,2300,63,39,\"\",\"11100000\",\"00000110\",\"01001001\",1\r\nOK\r\n"),
+            Err(Error::ExtraParameter)
+        );
+        assert_eq!(XMonitor::parse(&XMONITOR[..60]), Err(Error::Truncated));
+        assert_eq!(
+            XMonitor::parse("%XMONITOR:\r\nOK\r\n"),
+            Err(Error::MissingParameter)
+        );
+    }
+
+    #[test]
+    fn cpsms() {
+        let cpsms = Cpsms::parse("+CPSMS: 1,,,\"10101111\",\"01101100\"\r\nOK\r\n").unwrap();
+        assert!(cpsms.enabled);
+        assert_eq!(cpsms.periodic_tau, Some(0b1010_1111));
+        assert_eq!(cpsms.active_time, Some(0b0110_1100));
+
+        let disabled = Cpsms::parse("+CPSMS: 0\r\nOK\r\n").unwrap();
+        assert!(!disabled.enabled);
+        assert_eq!(disabled.periodic_tau, None);
+    }
+
+    #[test]
+    fn cpsms_bad_fields() {
+        assert_eq!(
+            Cpsms::parse("+CPSMS: 2\r\nOK\r\n"),
+            Err(Error::InvalidParameter)
+        );
+        assert_eq!(
+            Cpsms::parse("+CPSMS: 1,,,\"10101121\",\"01101100\"\r\nOK\r\n"),
+            Err(Error::InvalidParameter)
+        );
+        assert_eq!(
+            Cpsms::parse("+CPSMS: 1,,,\"10101111\",\"01101100\",\"1\"\r\nOK\r\n"),
+            Err(Error::ExtraParameter)
+        );
+        assert_eq!(
+            Cpsms::parse("+CPSMS:\r\nOK\r\n"),
+            Err(Error::MissingParameter)
+        );
+        assert_eq!(Cpsms::parse("+CPSMS: 1,,,\"1010"), Err(Error::Truncated));
+    }
+
+    #[test]
+    fn cpsms_command() {
+        assert_eq!(
+            Cpsms::command(Some((0b1010_1111, 0b0110_1100))).as_str(),
+            "AT+CPSMS=1,,,\"10101111\",\"01101100\""
+        );
+        assert_eq!(Cpsms::command(None).as_str(), "AT+CPSMS=0");
+    }
+
+    #[test]
+    fn gprs_timer_round_trip() {
+        // Largest value of each unit, and one second more rounds up into the next unit
+        for (timer, secs, next) in [
+            (GprsTimer::PeriodicTau, 62, 90),
+            (GprsTimer::PeriodicTau, 930, 960),
+            (GprsTimer::PeriodicTau, 1860, 2400),
+            (GprsTimer::PeriodicTau, 18600, 21600),
+            (GprsTimer::PeriodicTau, 111600, 144000),
+            (GprsTimer::PeriodicTau, 1116000, 1152000),
+            (GprsTimer::ActiveTime, 62, 120),
+            (GprsTimer::ActiveTime, 1860, 2160),
+        ] {
+            assert_eq!(timer.decode(timer.encode(secs)), Some(secs));
+            assert_eq!(timer.decode(timer.encode(secs + 1)), Some(next));
+        }
+        assert_eq!(GprsTimer::PeriodicTau.encode(3600), 0b000_00110);
+        assert_eq!(GprsTimer::ActiveTime.encode(10), 0b000_00101);
+        assert_eq!(GprsTimer::ActiveTime.decode(0), Some(0));
+    }
+
+    #[test]
+    fn gprs_timer_limits() {
+        // Longer than the largest unit holds
+        assert_eq!(GprsTimer::PeriodicTau.encode(u32::MAX), 0b110_11111);
+        assert_eq!(GprsTimer::ActiveTime.encode(11161), 0b010_11111);
+        assert_eq!(GprsTimer::ActiveTime.decode(0b010_11111), Some(11160));
+        // Deactivated, and a unit the active time does not have
+        assert_eq!(GprsTimer::PeriodicTau.decode(0b111_00001), None);
+        assert_eq!(GprsTimer::ActiveTime.decode(0b111_00000), None);
+        assert_eq!(GprsTimer::ActiveTime.decode(0b011_00001), None);
+    }
+
+    #[test]
+    fn network_time() {
+        let time = NetworkTime::parse("+CCLK: \"18/12/06,22:10:00+08\"\r\nOK\r\n").unwrap();
+        assert_eq!(
+            time,
+            NetworkTime {
+                year: 2018,
+                month: 12,
+                day: 6,
+                hour: 22,
+                minute: 10,
+                second: 0,
+                timezone: 8,
+            }
+        );
+        let time = NetworkTime::parse("+CCLK: \"23/01/31,05:59:59-20\"\r\nOK\r\n").unwrap();
+        assert_eq!(time.timezone, -20);
+    }
+
+    #[test]
+    fn network_time_bad_fields() {
+        for bad in [
+            "+CCLK: \"18/13/06,22:10:00+08\"\r\nOK\r\n",
+            "+CCLK: \"18/12/06,24:10:00+08\"\r\nOK\r\n",
+            "+CCLK: \"18/12/06,22:10:00+60\"\r\nOK\r\n",
+            "+CCLK: \"18-12-06,22:10:00+08\"\r\nOK\r\n",
+            "+CCLK: \"18/12/06,22:10:00\"\r\nOK\r\n",
+            "+CCLK: 18\r\nOK\r\n",
+        ] {
+            assert_eq!(
+                NetworkTime::parse(bad),
+                Err(Error::InvalidParameter),
+                "{}",
+                bad
+            );
+        }
+        assert_eq!(
+            NetworkTime::parse("+CCLK:\r\nOK\r\n"),
+            Err(Error::MissingParameter)
+        );
+        assert_eq!(
+            NetworkTime::parse("+CCLK: \"18/12/06,22:10:00+08\",1\r\nOK\r\n"),
+            Err(Error::ExtraParameter)
+        );
+        assert_eq!(
+            NetworkTime::parse("+CCLK: \"18/12/06,22:10"),
+            Err(Error::Truncated)
+        );
+    }
+
+    #[test]
+    fn imei() {
+        assert_eq!(
+            parse_imei("+CGSN: \"352656100367872\"\r\nOK\r\n")
+                .unwrap()
+                .as_str(),
+            "352656100367872"
+        );
+        assert_eq!(
+            parse_imei("+CGSN: \"35265610036787\"\r\nOK\r\n"),
+            Err(Error::InvalidParameter)
+        );
+        assert_eq!(
+            parse_imei("+CGSN: \"35265610036787A\"\r\nOK\r\n"),
+            Err(Error::InvalidParameter)
+        );
+        assert_eq!(
+            parse_imei("+CGSN: 352656100367872\r\nOK\r\n"),
+            Err(Error::InvalidParameter)
+        );
+        assert_eq!(
+            parse_imei("+CGSN: \"352656100367872\",1\r\nOK\r\n"),
+            Err(Error::ExtraParameter)
+        );
+        assert_eq!(parse_imei("+CGSN:\r\nOK\r\n"), Err(Error::MissingParameter));
+        assert_eq!(parse_imei("+CGSN: \"3526561"), Err(Error::Truncated));
+        assert_eq!(parse_imei("+CME ERROR: 3\r\n"), Err(Error::CmeError(3)));
+    }
+
+    #[test]
+    fn iccid() {
+        assert_eq!(
+            parse_iccid("%XICCID: 8901234567012345678F\r\nOK\r\n")
+                .unwrap()
+                .as_str(),
+            "8901234567012345678"
+        );
+        assert_eq!(
+            parse_iccid("%XICCID: 89012345670123456789\r\nOK\r\n")
+                .unwrap()
+                .as_str(),
+            "89012345670123456789"
+        );
+        assert_eq!(
+            parse_iccid("%XICCID: 890123456701234567890\r\nOK\r\n"),
+            Err(Error::InvalidParameter)
+        );
+        assert_eq!(parse_iccid("%XICCID: 8901"), Err(Error::Truncated));
+    }
+
+    #[test]
+    fn functional_mode() {
+        assert_eq!(
+            FunctionalMode::parse("+CFUN: 1\r\nOK\r\n"),
+            Ok(FunctionalMode::Normal)
+        );
+        assert_eq!(
+            FunctionalMode::parse("+CFUN: 21\r\nOK\r\n"),
+            Ok(FunctionalMode::LteOn)
+        );
+        assert_eq!(
+            FunctionalMode::parse("+CFUN: 2\r\nOK\r\n"),
+            Err(Error::InvalidParameter)
+        );
+        assert_eq!(FunctionalMode::LteOff.command().as_str(), "AT+CFUN=20");
+    }
+
+    #[test]
+    fn cmng() {
+        let sha = "2C43952EE9E000FF2ACC4E2ED0897C0A72AD5FA72C3D934E81741CBD54F05BD1";
+        let response = format!("%CMNG: 16842753,3,\"{}\"\r\nOK\r\n", sha);
+        assert_eq!(
+            Cmng::parse(&response),
+            Ok(Some(Cmng {
+                tag: 16842753,
+                ty: CredentialType::Psk
+            }))
+        );
+        assert_eq!(Cmng::parse("OK\r\n"), Ok(None));
+        assert_eq!(
+            Cmng::parse("%CMNG: 16842753,5\r\nOK\r\n"),
+            Err(Error::InvalidParameter)
+        );
+        assert_eq!(
+            Cmng::parse("%CMNG: 16842753,3,\"2C43\"\r\nOK\r\n"),
+            Err(Error::InvalidParameter)
+        );
+        assert_eq!(
+            Cmng::parse(&format!("%CMNG: 16842753,3,\"{}\",1\r\nOK\r\n", sha)),
+            Err(Error::ExtraParameter)
+        );
+        assert_eq!(
+            Cmng::parse("%CMNG: 16842753\r\nOK\r\n"),
+            Err(Error::MissingParameter)
+        );
+        assert_eq!(Cmng::parse("%CMNG: 1684"), Err(Error::Truncated));
+        assert_eq!(
+            Cmng::parse("+CME ERROR: 514\r\n"),
+            Err(Error::CmeError(514))
+        );
+    }
+
+    #[test]
+    fn cmng_commands() {
+        assert_eq!(
+            Cmng::write_command(1, CredentialType::PskId, "device-1")
+                .unwrap()
+                .as_str(),
+            "AT%CMNG=0,1,4,\"device-1\""
+        );
+        assert_eq!(
+            Cmng::delete_command(1, CredentialType::Psk).as_str(),
+            "AT%CMNG=3,1,3"
+        );
+        assert_eq!(
+            Cmng::list_command(1, CredentialType::Psk).as_str(),
+            "AT%CMNG=1,1,3"
+        );
+        assert_eq!(
+            Cmng::write_command(1, CredentialType::Psk, "ab\"cd"),
+            Err(Error::InvalidParameter)
+        );
+        assert_eq!(
+            Cmng::write_command(1, CredentialType::Psk, ""),
+            Err(Error::InvalidParameter)
+        );
+        let long = "a".repeat(MAX_CREDENTIAL_LEN + 1);
+        assert_eq!(
+            Cmng::write_command(1, CredentialType::Psk, &long),
+            Err(Error::InvalidParameter)
+        );
+        let longest = "a".repeat(MAX_CREDENTIAL_LEN);
+        assert!(Cmng::write_command(u32::MAX, CredentialType::Psk, &longest).is_ok());
+    }
+
+    const NCELLMEAS: &str = "%NCELLMEAS: 0,\"00011B07\",\"26295\",\"00B7\",10512,9034,7,63,31,150344527,2300,8,60,29,0,2400,11,55,26,184,150344535\r\n";
+
+    #[test]
+    fn ncellmeas() {
+        let cells = Ncellmeas::parse(NCELLMEAS).unwrap().unwrap();
+        assert_eq!(cells.serving.cell_id.as_str(), "00011B07");
+        assert_eq!(cells.serving.plmn.as_str(), "26295");
+        assert_eq!(cells.serving.tac.as_str(), "00B7");
+        assert_eq!(cells.serving.timing_advance, Some(10512));
+        assert_eq!(cells.serving.earfcn, 9034);
+        assert_eq!(cells.serving.phys_cell_id, 7);
+        assert_eq!(cells.serving.rsrp, Some(-77));
+        assert_eq!(cells.serving.rsrq, Some(-4.5));
+        assert_eq!(cells.neighbors.len(), 2);
+        assert_eq!(
+            cells.neighbors[1],
+            NeighborCell {
+                earfcn: 2400,
+                phys_cell_id: 11,
+                rsrp: Some(-85),
+                rsrq: Some(-7.0),
+            }
+        );
+    }
+
+    #[test]
+    fn ncellmeas_no_neighbors() {
+        let cells = Ncellmeas::parse(
+            "%NCELLMEAS: 0,\"00011B07\",\"26295\",\"00B7\",65535,9034,7,255,255,150344527,0\r\n",
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(cells.serving.timing_advance, None);
+        assert_eq!(cells.serving.rsrp, None);
+        assert!(cells.neighbors.is_empty());
+    }
+
+    #[test]
+    fn ncellmeas_failed() {
+        assert_eq!(Ncellmeas::parse("%NCELLMEAS: 1\r\n"), Ok(None));
+        assert_eq!(Ncellmeas::parse("%NCELLMEAS: 2\r\n"), Ok(None));
+        assert_eq!(
+            Ncellmeas::parse("%NCELLMEAS: 3\r\n"),
+            Err(Error::InvalidParameter)
+        );
+    }
+
+    #[test]
+    fn ncellmeas_keeps_max_neighbors() {
+        let mut notification = std::string::String::from(
+            "%NCELLMEAS: 0,\"00011B07\",\"26295\",\"00B7\",10512,9034,7,63,31,1",
+        );
+        for pci in 0..MAX_NEIGHBORS + 4 {
+            notification.push_str(&format!(",2300,{},60,29,0", pci));
+        }
+        notification.push_str(",2\r\n");
+        let cells = Ncellmeas::parse(&notification).unwrap().unwrap();
+        assert_eq!(cells.neighbors.len(), MAX_NEIGHBORS);
+        assert_eq!(cells.neighbors[MAX_NEIGHBORS - 1].phys_cell_id, 7);
+    }
+
+    #[test]
+    fn ncellmeas_longest_fits() {
+        let mut notification = std::string::String::from(
+            "%NCELLMEAS: 0,\"FFFFFFFF\",\"999999\",\"FFFF\",20512,262143,503,255,255,18446744073709551615",
+        );
+        for _ in 0..17 {
+            notification.push_str(",262143,503,255,255,-2147483648");
+        }
+        notification.push_str(",18446744073709551615\r\n");
+        assert!(notification.len() <= NCELLMEAS_MAX_LEN);
+        assert!(Ncellmeas::parse(&notification).unwrap().is_some());
+    }
+
+    #[test]
+    fn ncellmeas_bad_fields() {
+        // Neighbor cut to 4 fields
+        assert_eq!(
+            Ncellmeas::parse("%NCELLMEAS: 0,\"00011B07\",\"26295\",\"00B7\",10512,9034,7,63,31,1,2300,8,60,29,2\r\n"),
+            Err(Error::MissingParameter)
+        );
+        // Timing advance measurement time missing
+        assert_eq!(
+            Ncellmeas::parse(
+                "%NCELLMEAS: 0,\"00011B07\",\"26295\",\"00B7\",10512,9034,7,63,31,1\r\n"
+            ),
+            Err(Error::MissingParameter)
+        );
+        // Physical cell ID out of range
+        assert_eq!(
+            Ncellmeas::parse(
+                "%NCELLMEAS: 0,\"00011B07\",\"26295\",\"00B7\",10512,9034,504,63,31,1,0\r\n"
+            ),
+            Err(Error::InvalidParameter)
+        );
+        // Cell ID too long
+        assert_eq!(
+            Ncellmeas::parse(
+                "%NCELLMEAS: 0,\"00011B070\",\"26295\",\"00B7\",10512,9034,7,63,31,1,0\r\n"
+            ),
+            Err(Error::InvalidParameter)
+        );
+        // Cut short by the notification buffer
+        assert_eq!(
+            Ncellmeas::parse(&NCELLMEAS[..NCELLMEAS.len() - 10]),
+            Err(Error::Truncated)
+        );
+    }
+
+    #[test]
+    fn edrx() {
+        let edrx = Edrx::parse("+CEDRXRDP: 4,\"1000\",\"0101\",\"1011\"\r\nOK\r\n").unwrap();
+        assert_eq!(
+            edrx,
+            Edrx {
+                act: 4,
+                edrx: Some(0b0101),
+                ptw: Some(0b1011)
+            }
+        );
+        assert_eq!(
+            Edrx::parse("+CEDRXRDP: 0\r\nOK\r\n"),
+            Ok(Edrx {
+                act: 0,
+                edrx: None,
+                ptw: None
+            })
+        );
+        assert_eq!(
+            Edrx::parse_notification("+CEDRXP: 4,\"1000\",\"0101\",\"1011\"\r\n")
+                .unwrap()
+                .edrx,
+            Some(0b0101)
+        );
+        assert_eq!(
+            Edrx::parse("+CEDRXRDP: 4,\"1000\",\"01010\",\"1011\"\r\nOK\r\n"),
+            Err(Error::InvalidParameter)
+        );
+        assert_eq!(
+            Edrx::parse("+CEDRXRDP: 7\r\nOK\r\n"),
+            Err(Error::InvalidParameter)
+        );
+        assert_eq!(
+            Edrx::parse("+CEDRXRDP: 4,\"1000\",\"0101\",\"1011\",\"1\"\r\nOK\r\n"),
+            Err(Error::ExtraParameter)
+        );
+        assert_eq!(
+            Edrx::parse_notification("+CEDRXP: 4,\"1000\""),
+            Err(Error::Truncated)
+        );
+    }
+
+    #[test]
+    fn registration_status_serializes_snake_case() {
+        assert_eq!(
+            serde_json::to_string(&RegistrationStatus::SimFailure).unwrap(),
+            "\"sim_failure\""
+        );
+    }
+}
@@ -0,0 +1,165 @@
+//! Sensor fault classification.  Hall sensor inputs are pulled down, so a reading taken before
+//! power up sits at a known level and a sensor that does not respond to power up is told apart
+//! from a working one.
+use serde::Serialize;
+
+/// Sensor fault status, reported with every sample
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[serde(rename_all = "snake_case")]
+pub enum SensorFault {
+    None,
+    OpenCircuit,
+    Short,
+    StuckAt,
+    OutOfRange,
+    /// The reading did not change on power up, or no reading could be taken
+    NoPowerUpResponse,
+}
+
+impl SensorFault {
+    pub fn is_fault(&self) -> bool {
+        *self != SensorFault::None
+    }
+}
+
+/// Fault detection thresholds, in raw ADC values
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FaultParams {
+    /// At or below, the input is at its pulled down level: an open circuit
+    pub open_max: i16,
+    /// At or above, the sensor is shorted to its supply
+    pub short_min: i16,
+    /// Minimum change between the idle and the powered reading
+    pub min_response: u16,
+    /// Consecutive identical readings before the sensor is considered stuck
+    pub stuck_samples: u8,
+}
+
+/// Classifies raw hall sensor readings into fault conditions
+#[derive(Debug, Clone)]
+pub struct FaultDetector {
+    params: FaultParams,
+    cal_min: i16,
+    cal_max: i16,
+    last_raw: Option<i16>,
+    repeats: u8,
+}
+
+impl FaultDetector {
+    /// `cal_min..=cal_max` is the calibrated range, readings outside are not trusted
+    pub fn new(params: FaultParams, cal_min: i16, cal_max: i16) -> Self {
+        FaultDetector {
+            params,
+            cal_min,
+            cal_max,
+            last_raw: None,
+            repeats: 0,
+        }
+    }
+
+    /// Classify the reading `raw` taken after power up.  `idle` is the reading taken before, `None`
+    /// skips the power up check
+    pub fn classify(&mut self, idle: Option<i16>, raw: i16) -> SensorFault {
+        if self.last_raw == Some(raw) {
+            self.repeats = self.repeats.saturating_add(1);
+        } else {
+            self.repeats = 0;
+        }
+        self.last_raw = Some(raw);
+
+        let params = &self.params;
+        if raw <= params.open_max {
+            SensorFault::OpenCircuit
+        } else if raw >= params.short_min {
+            SensorFault::Short
+        } else if matches!(idle, Some(idle) if raw.abs_diff(idle) < params.min_response) {
+            SensorFault::NoPowerUpResponse
+        } else if self.repeats >= params.stuck_samples {
+            SensorFault::StuckAt
+        } else if !(self.cal_min..=self.cal_max).contains(&raw) {
+            SensorFault::OutOfRange
+        } else {
+            SensorFault::None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PARAMS: FaultParams = FaultParams {
+        open_max: 100,
+        short_min: 3900,
+        min_response: 200,
+        stuck_samples: 3,
+    };
+
+    fn detector() -> FaultDetector {
+        FaultDetector::new(PARAMS, 800, 2800)
+    }
+
+    #[test]
+    fn healthy() {
+        let mut faults = detector();
+        assert_eq!(faults.classify(Some(20), 1500), SensorFault::None);
+        assert_eq!(faults.classify(None, 1501), SensorFault::None);
+    }
+
+    #[test]
+    fn open_circuit() {
+        let mut faults = detector();
+        assert_eq!(faults.classify(Some(10), 100), SensorFault::OpenCircuit);
+        assert_eq!(faults.classify(Some(10), 0), SensorFault::OpenCircuit);
+    }
+
+    #[test]
+    fn short() {
+        let mut faults = detector();
+        assert_eq!(faults.classify(Some(3950), 3900), SensorFault::Short);
+        assert_eq!(faults.classify(Some(20), 4095), SensorFault::Short);
+    }
+
+    #[test]
+    fn no_power_up_response() {
+        let mut faults = detector();
+        // Driven while unpowered and unchanged by power up
+        assert_eq!(
+            faults.classify(Some(1400), 1599),
+            SensorFault::NoPowerUpResponse
+        );
+        assert_eq!(
+            faults.classify(Some(1600), 1401),
+            SensorFault::NoPowerUpResponse
+        );
+        assert_eq!(faults.classify(Some(1400), 1600), SensorFault::None);
+    }
+
+    #[test]
+    fn stuck() {
+        let mut faults = detector();
+        for _ in 0..PARAMS.stuck_samples {
+            assert_eq!(faults.classify(Some(20), 1500), SensorFault::None);
+        }
+        assert_eq!(faults.classify(Some(20), 1500), SensorFault::StuckAt);
+        // Any change clears it
+        assert_eq!(faults.classify(Some(20), 1501), SensorFault::None);
+    }
+
+    #[test]
+    fn out_of_range() {
+        let mut faults = detector();
+        assert_eq!(faults.classify(Some(20), 799), SensorFault::OutOfRange);
+        assert_eq!(faults.classify(Some(20), 2801), SensorFault::OutOfRange);
+        assert_eq!(faults.classify(Some(20), 800), SensorFault::None);
+        assert_eq!(faults.classify(Some(20), 2800), SensorFault::None);
+    }
+
+    #[test]
+    fn open_takes_precedence() {
+        let mut faults = detector();
+        // Unpowered and unplugged both read the pulled down level, report the wiring
+        assert_eq!(faults.classify(Some(5), 5), SensorFault::OpenCircuit);
+    }
+}
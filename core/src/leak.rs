@@ -0,0 +1,230 @@
+//! Leak detection from the tank level drop rate.  The rate is measured over windows of hours, a
+//! level sample is quantized so over seconds a single step of flicker would read as a huge rate.
+//! A drop must also stay abnormal for several windows in a row before it raises an alarm.
+
+/// Leak detection settings
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LeakParams {
+    /// Length of a rate window (seconds)
+    pub window_secs: u32,
+    /// Consecutive windows the drop rate must exceed the alarm level before an alarm
+    pub confirm_windows: u8,
+    /// Drop rate (%/hour) that always raises an alarm, regardless of learned consumption
+    pub min_rate: f32,
+    /// Alarm when the drop rate exceeds the learned baseline by this factor
+    pub alarm_factor: f32,
+    /// Clear the alarm once the drop rate falls below the learned baseline by this factor
+    pub clear_factor: f32,
+    /// Weight given to each new window when learning the baseline consumption rate
+    pub baseline_weight: f32,
+    /// Level resolution (%), the error of a level reading is up to one step
+    pub step: u32,
+}
+
+/// Abnormal drop rate reported by `LeakDetector`, both in %/hour
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Leak {
+    pub rate: f32,
+    pub baseline: f32,
+}
+
+/// Compares the drop rate of each window against the learned baseline consumption rate.  The
+/// alarm is latched until the rate falls back below the clear level so a single leak only raises
+/// one alarm.
+#[derive(Debug, Clone)]
+pub struct LeakDetector {
+    params: LeakParams,
+    /// Level and timestamp at the start of the current window
+    start: Option<(u32, u32)>,
+    /// Consecutive windows above the alarm level
+    high: u8,
+    baseline: Option<f32>,
+    alarm: bool,
+}
+
+impl LeakDetector {
+    pub fn new(params: LeakParams) -> Self {
+        LeakDetector {
+            params,
+            start: None,
+            high: 0,
+            baseline: None,
+            alarm: false,
+        }
+    }
+
+    /// Learned baseline consumption rate in percent per hour
+    pub fn baseline(&self) -> Option<f32> {
+        self.baseline
+    }
+
+    /// Start a new window, used after a refill so the jump is not seen as a rate change
+    pub fn reset(&mut self) {
+        self.start = None;
+        self.high = 0;
+        self.alarm = false;
+    }
+
+    /// Feed a new sample, returns the leak when the drop rate first stayed above the alarm level
+    /// for `confirm_windows` windows
+    pub fn update(&mut self, level: u32, timestamp: u32) -> Option<Leak> {
+        let (start_level, start_time) = *self.start.get_or_insert((level, timestamp));
+        let elapsed = match timestamp.checked_sub(start_time) {
+            Some(elapsed) if elapsed >= self.params.window_secs => elapsed,
+            Some(_) => return None,
+            // Clock went back, start over
+            None => {
+                self.start = Some((level, timestamp));
+                return None;
+            }
+        };
+        self.start = Some((level, timestamp));
+
+        // Both readings may be off by a step, so only the drop beyond one step is certain
+        let drop = start_level
+            .saturating_sub(level)
+            .saturating_sub(self.params.step);
+        let rate = drop as f32 * 3600.0 / elapsed as f32;
+        self.window(rate)
+    }
+
+    fn window(&mut self, rate: f32) -> Option<Leak> {
+        let params = &self.params;
+        let baseline = self.baseline.unwrap_or(0.0);
+
+        if self.alarm {
+            if rate < (baseline * params.clear_factor).max(params.min_rate) {
+                self.alarm = false;
+            }
+            return None;
+        }
+
+        if rate > (baseline * params.alarm_factor).max(params.min_rate) {
+            self.high += 1;
+            if self.high < params.confirm_windows {
+                return None;
+            }
+            self.high = 0;
+            self.alarm = true;
+            return Some(Leak { rate, baseline });
+        }
+        self.high = 0;
+
+        // Only normal consumption is used to learn the baseline
+        self.baseline = Some(match self.baseline {
+            Some(baseline) => baseline + params.baseline_weight * (rate - baseline),
+            None => rate,
+        });
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PARAMS: LeakParams = LeakParams {
+        window_secs: 3600,
+        confirm_windows: 3,
+        min_rate: 2.0,
+        alarm_factor: 5.0,
+        clear_factor: 2.0,
+        baseline_weight: 0.1,
+        step: 1,
+    };
+    /// Sample interval (seconds)
+    const INTERVAL: u32 = 3;
+
+    /// Feed `hours` of samples with the level given by `level(t)`, returns the alarms raised
+    fn run(detector: &mut LeakDetector, hours: u32, level: impl Fn(u32) -> u32) -> Vec<Leak> {
+        (0..hours * 3600 / INTERVAL)
+            .map(|i| i * INTERVAL)
+            .filter_map(|t| detector.update(level(t), t))
+            .collect()
+    }
+
+    #[test]
+    fn flicker_is_not_a_leak() {
+        let mut detector = LeakDetector::new(PARAMS);
+        // One step up and down every sample
+        let alarms = run(&mut detector, 24, |t| 50 - (t / INTERVAL) % 2);
+        assert!(alarms.is_empty());
+        assert_eq!(detector.baseline(), Some(0.0));
+    }
+
+    #[test]
+    fn single_step_drop_is_not_a_leak() {
+        let mut detector = LeakDetector::new(PARAMS);
+        // Level steps down right after the window starts
+        let alarms = run(&mut detector, 2, |t| if t < 6 { 50 } else { 49 });
+        assert!(alarms.is_empty());
+    }
+
+    #[test]
+    fn slow_natural_decline_is_not_a_leak() {
+        let mut detector = LeakDetector::new(PARAMS);
+        // 1.5 %/hour for two days
+        let alarms = run(&mut detector, 48, |t| 90 - t * 3 / 7200);
+        assert!(alarms.is_empty());
+        let baseline = detector.baseline().unwrap();
+        assert!(baseline > 0.0 && baseline < 1.5, "baseline {}", baseline);
+    }
+
+    #[test]
+    fn sustained_leak_alarms_once() {
+        let mut detector = LeakDetector::new(PARAMS);
+        // Normal use for a day, then 10 %/hour for 5 hours
+        let level = |t: u32| match t {
+            t if t < 24 * 3600 => 90 - t / 3600,
+            t => 66 - (t - 24 * 3600) * 10 / 3600,
+        };
+        let alarms = run(&mut detector, 29, level);
+        assert_eq!(alarms.len(), 1);
+        assert!(alarms[0].rate > 8.0, "rate {}", alarms[0].rate);
+    }
+
+    #[test]
+    fn short_burst_is_not_a_leak() {
+        let mut detector = LeakDetector::new(PARAMS);
+        // A single hour of heavy use between normal days
+        let level = |t: u32| match t {
+            t if t < 24 * 3600 => 90,
+            t if t < 25 * 3600 => 90 - (t - 24 * 3600) * 10 / 3600,
+            _ => 80,
+        };
+        assert!(run(&mut detector, 48, level).is_empty());
+    }
+
+    #[test]
+    fn alarm_clears_and_rearms() {
+        let mut detector = LeakDetector::new(PARAMS);
+        detector.update(90, 0);
+        let mut time = 0;
+        // Level at the end of each window, returns whether it raised an alarm
+        let mut window = |level: u32| {
+            time += PARAMS.window_secs;
+            detector.update(level, time).is_some()
+        };
+        assert!(!window(80));
+        assert!(!window(70));
+        assert!(window(60));
+        // Latched while the drop continues
+        assert!(!window(50));
+        // Cleared by a normal window, alarms again after the confirm windows
+        assert!(!window(50));
+        assert!(!window(40));
+        assert!(!window(30));
+        assert!(window(20));
+    }
+
+    #[test]
+    fn reset_forgets_the_window() {
+        let mut detector = LeakDetector::new(PARAMS);
+        detector.update(90, 0);
+        detector.reset();
+        // A refill must not look like a drop, nor a drop from before the refill
+        detector.update(20, 10);
+        assert_eq!(detector.update(20, 10 + PARAMS.window_secs), None);
+        assert_eq!(detector.baseline(), Some(0.0));
+    }
+}
@@ -0,0 +1,10 @@
+//! Hardware independent parts of the propane monitor firmware, kept out of the firmware crate so
+//! they build and are tested on the host
+#![cfg_attr(not(test), no_std)]
+
+pub mod at;
+pub mod fault;
+pub mod leak;
+pub mod modbus;
+pub mod sensor;
+pub mod ultrasonic;
@@ -0,0 +1,277 @@
+//! Modbus RTU frames for reading holding and input registers.  Encoding and decoding do not touch
+//! the hardware, the firmware's `modbus` module sends and receives them.
+use heapless::Vec;
+
+/// Most registers read with a single request
+pub const MAX_REGISTERS: usize = 16;
+/// Request frame: slave, function, address, count, CRC
+pub const REQUEST_LEN: usize = 8;
+/// Response header: slave, function, byte count (or exception code)
+pub const HEADER_LEN: usize = 3;
+pub const CRC_LEN: usize = 2;
+/// Set on the function code of an exception response
+const EXCEPTION: u8 = 0x80;
+
+/// Response frame errors
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum FrameError {
+    /// The frame failed CRC validation
+    Checksum,
+    /// The frame is too short or does not answer the request
+    InvalidFrame,
+    /// Exception response with the exception code
+    Exception(u8),
+}
+
+/// Modbus register table
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum RegisterKind {
+    /// Function 0x03
+    Holding,
+    /// Function 0x04
+    Input,
+}
+
+impl RegisterKind {
+    fn function(&self) -> u8 {
+        match self {
+            RegisterKind::Holding => 0x03,
+            RegisterKind::Input => 0x04,
+        }
+    }
+}
+
+/// Interpretation of the register(s) of a point, 32 bit values are high word first
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum RegisterFormat {
+    U16,
+    I16,
+    U32,
+    I32,
+}
+
+impl RegisterFormat {
+    /// Number of registers holding the value
+    pub fn count(&self) -> u16 {
+        match self {
+            RegisterFormat::U16 | RegisterFormat::I16 => 1,
+            RegisterFormat::U32 | RegisterFormat::I32 => 2,
+        }
+    }
+
+    /// Convert the registers into a value
+    pub fn value(&self, registers: &[u16]) -> i64 {
+        match self {
+            RegisterFormat::U16 => registers[0] as i64,
+            RegisterFormat::I16 => registers[0] as i16 as i64,
+            RegisterFormat::U32 => ((registers[0] as u32) << 16 | registers[1] as u32) as i64,
+            RegisterFormat::I32 => {
+                ((registers[0] as u32) << 16 | registers[1] as u32) as i32 as i64
+            }
+        }
+    }
+}
+
+/// Modbus CRC-16 (polynomial 0xA001 reflected, initial value 0xFFFF), sent low byte first
+pub fn crc16(data: &[u8]) -> u16 {
+    let mut crc = 0xFFFF;
+    for byte in data {
+        crc ^= *byte as u16;
+        for _ in 0..8 {
+            if crc & 1 != 0 {
+                crc = (crc >> 1) ^ 0xA001;
+            } else {
+                crc >>= 1;
+            }
+        }
+    }
+    crc
+}
+
+/// Encode a read holding/input registers request
+pub fn encode_read_request(
+    slave: u8,
+    kind: RegisterKind,
+    address: u16,
+    count: u16,
+) -> [u8; REQUEST_LEN] {
+    let mut frame = [0; REQUEST_LEN];
+    frame[0] = slave;
+    frame[1] = kind.function();
+    frame[2..4].copy_from_slice(&address.to_be_bytes());
+    frame[4..6].copy_from_slice(&count.to_be_bytes());
+    let crc = crc16(&frame[..6]);
+    frame[6..].copy_from_slice(&crc.to_le_bytes());
+    frame
+}
+
+/// Number of bytes following the response header, from the header
+pub fn response_remaining(header: &[u8; HEADER_LEN]) -> usize {
+    if header[1] & EXCEPTION != 0 {
+        // Exception code is in the header
+        CRC_LEN
+    } else {
+        header[2] as usize + CRC_LEN
+    }
+}
+
+/// Decode a read registers response, checking it answers the request
+pub fn decode_read_response(
+    frame: &[u8],
+    slave: u8,
+    kind: RegisterKind,
+    count: u16,
+) -> Result<Vec<u16, MAX_REGISTERS>, FrameError> {
+    if frame.len() < HEADER_LEN + CRC_LEN || count as usize > MAX_REGISTERS {
+        return Err(FrameError::InvalidFrame);
+    }
+    let (data, crc) = frame.split_at(frame.len() - CRC_LEN);
+    if crc16(data).to_le_bytes() != crc {
+        return Err(FrameError::Checksum);
+    }
+    if data[0] != slave {
+        return Err(FrameError::InvalidFrame);
+    }
+    if data[1] == kind.function() | EXCEPTION && data.len() == HEADER_LEN {
+        return Err(FrameError::Exception(data[2]));
+    }
+    let len = count as usize * 2;
+    if data[1] != kind.function() || data[2] as usize != len || data.len() != HEADER_LEN + len {
+        return Err(FrameError::InvalidFrame);
+    }
+
+    Ok(data[HEADER_LEN..]
+        .chunks_exact(2)
+        .map(|word| u16::from_be_bytes([word[0], word[1]]))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn crc_of_known_request() {
+        assert_eq!(crc16(&[0x01, 0x03, 0x00, 0x00, 0x00, 0x0A]), 0xCDC5);
+    }
+
+    #[test]
+    fn encode_requests() {
+        assert_eq!(
+            encode_read_request(0x01, RegisterKind::Holding, 0x0000, 10),
+            [0x01, 0x03, 0x00, 0x00, 0x00, 0x0A, 0xC5, 0xCD]
+        );
+        assert_eq!(
+            encode_read_request(0x11, RegisterKind::Input, 0x0008, 2),
+            [0x11, 0x04, 0x00, 0x08, 0x00, 0x02, 0xF2, 0x99]
+        );
+    }
+
+    #[test]
+    fn decode_registers() {
+        let frame = [0x01, 0x03, 0x04, 0x00, 0x06, 0x00, 0x05, 0xDA, 0x31];
+        let registers = decode_read_response(&frame, 0x01, RegisterKind::Holding, 2).unwrap();
+        assert_eq!(registers.as_slice(), &[0x0006, 0x0005]);
+
+        let frame = [0x11, 0x04, 0x04, 0xFF, 0xFF, 0xFF, 0xFE, 0x2A, 0x11];
+        let registers = decode_read_response(&frame, 0x11, RegisterKind::Input, 2).unwrap();
+        assert_eq!(RegisterFormat::I32.value(&registers), -2);
+        assert_eq!(RegisterFormat::U32.value(&registers), 0xFFFF_FFFE);
+    }
+
+    #[test]
+    fn decode_exception() {
+        let frame = [0x01, 0x83, 0x02, 0xC0, 0xF1];
+        assert_eq!(response_remaining(&[0x01, 0x83, 0x02]), CRC_LEN);
+        assert_eq!(
+            decode_read_response(&frame, 0x01, RegisterKind::Holding, 10),
+            Err(FrameError::Exception(0x02))
+        );
+        // Exception to another function is not an answer to the request
+        assert_eq!(
+            decode_read_response(&frame, 0x01, RegisterKind::Input, 10),
+            Err(FrameError::InvalidFrame)
+        );
+    }
+
+    #[test]
+    fn decode_bad_crc() {
+        let frame = [0x01, 0x03, 0x04, 0x00, 0x06, 0x00, 0x05, 0xDA, 0x30];
+        assert_eq!(
+            decode_read_response(&frame, 0x01, RegisterKind::Holding, 2),
+            Err(FrameError::Checksum)
+        );
+        let frame = [0x01, 0x03, 0x04, 0x00, 0x07, 0x00, 0x05, 0xDA, 0x31];
+        assert_eq!(
+            decode_read_response(&frame, 0x01, RegisterKind::Holding, 2),
+            Err(FrameError::Checksum)
+        );
+    }
+
+    #[test]
+    fn decode_wrong_slave_or_function() {
+        let frame = [0x01, 0x03, 0x04, 0x00, 0x06, 0x00, 0x05, 0xDA, 0x31];
+        assert_eq!(
+            decode_read_response(&frame, 0x02, RegisterKind::Holding, 2),
+            Err(FrameError::InvalidFrame)
+        );
+        assert_eq!(
+            decode_read_response(&frame, 0x01, RegisterKind::Input, 2),
+            Err(FrameError::InvalidFrame)
+        );
+        // Byte count does not match the requested registers
+        assert_eq!(
+            decode_read_response(&frame, 0x01, RegisterKind::Holding, 1),
+            Err(FrameError::InvalidFrame)
+        );
+    }
+
+    #[test]
+    fn decode_short_frames() {
+        for len in 0..HEADER_LEN + CRC_LEN {
+            let frame = [0x01, 0x03, 0x02, 0x00, 0x2A, 0x39, 0x9B];
+            assert_eq!(
+                decode_read_response(&frame[..len], 0x01, RegisterKind::Holding, 1),
+                Err(FrameError::InvalidFrame)
+            );
+        }
+        // Byte count claims more data than the frame holds
+        let frame = [0x01, 0x03, 0x04, 0x00, 0x2A, 0xD9, 0x9A];
+        assert_eq!(
+            decode_read_response(&frame, 0x01, RegisterKind::Holding, 2),
+            Err(FrameError::InvalidFrame)
+        );
+        let frame = [0x01, 0x03, 0x02, 0x00, 0x2A, 0x39, 0x9B];
+        assert_eq!(
+            decode_read_response(&frame, 0x01, RegisterKind::Holding, 1)
+                .unwrap()
+                .as_slice(),
+            &[0x002A]
+        );
+    }
+
+    #[test]
+    fn too_many_registers() {
+        let frame = [0x01, 0x03, 0x02, 0x00, 0x2A, 0x39, 0x9B];
+        assert_eq!(
+            decode_read_response(
+                &frame,
+                0x01,
+                RegisterKind::Holding,
+                MAX_REGISTERS as u16 + 1
+            ),
+            Err(FrameError::InvalidFrame)
+        );
+    }
+
+    #[test]
+    fn register_formats() {
+        assert_eq!(RegisterFormat::I16.value(&[0xFFFF]), -1);
+        assert_eq!(RegisterFormat::U16.value(&[0xFFFF]), 65535);
+        assert_eq!(RegisterFormat::U32.value(&[0x0001, 0x0002]), 0x0001_0002);
+        assert_eq!(RegisterFormat::U32.count(), 2);
+    }
+}
@@ -0,0 +1,248 @@
+//! Tank sensor drivers and the measurement sequence.  A driver implements `Sensor`, the firmware
+//! provides the ADC and the settle delay, so a new sensor only needs a `Sensor` implementation and
+//! the sequence is tested on the host with a mock ADC.
+use crate::fault::SensorFault;
+use core::future::Future;
+
+/// Source of raw samples, the SAADC on the device
+pub trait Adc {
+    /// Sample `channel` and return the raw value
+    fn sample(&mut self, channel: usize) -> i16;
+}
+
+/// Wait for the sensor to settle without blocking the other tasks
+pub trait Delay {
+    type Future: Future<Output = ()>;
+
+    fn delay_us(&mut self, us: u32) -> Self::Future;
+}
+
+/// A tank sensor driver, see `measure` for the order in which the methods are called
+pub trait Sensor {
+    /// Take a reading with the sensor unpowered, for the power up check in `check_fault`.  `None`
+    /// for sensors that cannot be read unpowered
+    fn read_idle(&mut self, _adc: &mut dyn Adc) -> Option<i16> {
+        None
+    }
+
+    /// Power up the sensor
+    fn power_up(&mut self);
+
+    /// Time to wait after power up before the reading is valid (us)
+    fn settle_us(&self) -> u32;
+
+    /// Take a raw reading from the powered sensor, `None` if no reading could be taken.  Sensors
+    /// that are not read through the ADC ignore `adc`
+    fn read(&mut self, adc: &mut dyn Adc) -> Option<i16>;
+
+    /// Power down the sensor
+    fn power_down(&mut self);
+
+    /// Convert a raw reading into a tank level percentage, `None` if the sensor does not measure
+    /// the level
+    fn level(&self, raw: i16) -> Option<u32>;
+
+    /// Convert a raw reading into a pressure (kPa), `None` if the sensor does not measure pressure
+    fn pressure(&self, _raw: i16) -> Option<u32> {
+        None
+    }
+
+    /// Classify a raw reading, `idle` is the reading from `read_idle`
+    fn check_fault(&mut self, idle: Option<i16>, raw: i16) -> SensorFault;
+}
+
+/// Result of a single sensor measurement
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Measurement {
+    pub raw: i16,
+    pub level: Option<u32>,
+    pub pressure: Option<u32>,
+    pub fault: SensorFault,
+}
+
+/// Run the measurement sequence: idle read, power up, settle, read, power down, fault check.  The
+/// sensor is always powered down, a failed reading is reported as a missing response
+pub async fn measure<S: Sensor + ?Sized, A: Adc, D: Delay>(
+    sensor: &mut S,
+    adc: &mut A,
+    delay: &mut D,
+) -> Measurement {
+    let idle = sensor.read_idle(adc);
+    sensor.power_up();
+    delay.delay_us(sensor.settle_us()).await;
+    let raw = sensor.read(adc);
+    sensor.power_down();
+
+    match raw {
+        Some(raw) => Measurement {
+            raw,
+            level: sensor.level(raw),
+            pressure: sensor.pressure(raw),
+            fault: sensor.check_fault(idle, raw),
+        },
+        None => Measurement {
+            raw: 0,
+            level: None,
+            pressure: None,
+            fault: SensorFault::NoPowerUpResponse,
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use std::future::{ready, Ready};
+    use std::pin::pin;
+    use std::rc::Rc;
+    use std::sync::Arc;
+    use std::task::{Context, Poll, Wake, Waker};
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    enum Step {
+        Idle,
+        PowerUp,
+        Settle(u32),
+        Sample(usize),
+        PowerDown,
+        CheckFault(Option<i16>, i16),
+    }
+
+    type Log = Rc<RefCell<Vec<Step>>>;
+
+    struct MockAdc {
+        log: Log,
+        value: i16,
+    }
+
+    impl Adc for MockAdc {
+        fn sample(&mut self, channel: usize) -> i16 {
+            self.log.borrow_mut().push(Step::Sample(channel));
+            self.value
+        }
+    }
+
+    struct MockDelay {
+        log: Log,
+    }
+
+    impl Delay for MockDelay {
+        type Future = Ready<()>;
+
+        fn delay_us(&mut self, us: u32) -> Ready<()> {
+            self.log.borrow_mut().push(Step::Settle(us));
+            ready(())
+        }
+    }
+
+    /// Reads channel 2 unless `fail` is set
+    struct MockSensor {
+        log: Log,
+        fail: bool,
+    }
+
+    impl Sensor for MockSensor {
+        fn read_idle(&mut self, _adc: &mut dyn Adc) -> Option<i16> {
+            self.log.borrow_mut().push(Step::Idle);
+            Some(10)
+        }
+
+        fn power_up(&mut self) {
+            self.log.borrow_mut().push(Step::PowerUp);
+        }
+
+        fn settle_us(&self) -> u32 {
+            500
+        }
+
+        fn read(&mut self, adc: &mut dyn Adc) -> Option<i16> {
+            (!self.fail).then(|| adc.sample(2))
+        }
+
+        fn power_down(&mut self) {
+            self.log.borrow_mut().push(Step::PowerDown);
+        }
+
+        fn level(&self, raw: i16) -> Option<u32> {
+            Some(raw as u32 / 10)
+        }
+
+        fn check_fault(&mut self, idle: Option<i16>, raw: i16) -> SensorFault {
+            self.log.borrow_mut().push(Step::CheckFault(idle, raw));
+            SensorFault::None
+        }
+    }
+
+    fn block_on<F: Future>(future: F) -> F::Output {
+        struct NoopWaker;
+        impl Wake for NoopWaker {
+            fn wake(self: Arc<Self>) {}
+        }
+        let waker = Waker::from(Arc::new(NoopWaker));
+        let mut cx = Context::from_waker(&waker);
+        let mut future = pin!(future);
+        loop {
+            if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+                return output;
+            }
+        }
+    }
+
+    fn run(fail: bool) -> (Measurement, Vec<Step>) {
+        let log = Log::default();
+        let mut sensor = MockSensor {
+            log: log.clone(),
+            fail,
+        };
+        let mut adc = MockAdc {
+            log: log.clone(),
+            value: 420,
+        };
+        let mut delay = MockDelay { log: log.clone() };
+        let measurement = block_on(measure(&mut sensor, &mut adc, &mut delay));
+        let steps = log.borrow().clone();
+        (measurement, steps)
+    }
+
+    #[test]
+    fn sequence_order() {
+        let (measurement, steps) = run(false);
+        assert_eq!(
+            steps,
+            [
+                Step::Idle,
+                Step::PowerUp,
+                Step::Settle(500),
+                Step::Sample(2),
+                Step::PowerDown,
+                Step::CheckFault(Some(10), 420),
+            ]
+        );
+        assert_eq!(
+            measurement,
+            Measurement {
+                raw: 420,
+                level: Some(42),
+                pressure: None,
+                fault: SensorFault::None,
+            }
+        );
+    }
+
+    #[test]
+    fn failed_read_powers_down() {
+        let (measurement, steps) = run(true);
+        assert_eq!(
+            steps,
+            [
+                Step::Idle,
+                Step::PowerUp,
+                Step::Settle(500),
+                Step::PowerDown
+            ]
+        );
+        assert_eq!(measurement.fault, SensorFault::NoPowerUpResponse);
+        assert_eq!(measurement.level, None);
+    }
+}
@@ -0,0 +1,79 @@
+//! Frames of the ultrasonic level sensor (DYP-A02 style protocol): 0xFF, distance high byte,
+//! distance low byte and checksum, the low byte of the sum of the first three.
+use crate::fault::SensorFault;
+
+/// Length of a frame
+pub const FRAME_LEN: usize = 4;
+/// Shortest distance (mm) the sensor can measure, readings below are not trusted
+pub const BLIND_ZONE_MM: i16 = 30;
+/// Frame start byte
+const HEADER: u8 = 0xFF;
+
+/// Find the first frame with a valid checksum and return its distance (mm).  The sensor streams
+/// frames, so `buf` may start in the middle of one
+pub fn parse_frame(buf: &[u8]) -> Option<u16> {
+    buf.windows(FRAME_LEN).find_map(|frame| {
+        let sum = frame[0].wrapping_add(frame[1]).wrapping_add(frame[2]);
+        if frame[0] == HEADER && frame[3] == sum {
+            Some(u16::from_be_bytes([frame[1], frame[2]]))
+        } else {
+            None
+        }
+    })
+}
+
+/// Classify a distance reading (mm) of a sensor below the tank.  The liquid height is the
+/// distance plus `offset_mm`, it cannot be above the tank height
+pub fn check_distance(distance_mm: i16, offset_mm: i32, height_mm: u32) -> SensorFault {
+    if distance_mm < BLIND_ZONE_MM || distance_mm as i64 + offset_mm as i64 > height_mm as i64 {
+        SensorFault::OutOfRange
+    } else {
+        SensorFault::None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn good_frame() {
+        assert_eq!(parse_frame(&[0xFF, 0x07, 0xA1, 0xA7]), Some(1953));
+        // Starts mid frame, the second frame is complete
+        assert_eq!(
+            parse_frame(&[0xA1, 0xA7, 0xFF, 0x02, 0x10, 0x11, 0xFF, 0x02]),
+            Some(528)
+        );
+        // Checksum wraps around
+        assert_eq!(parse_frame(&[0xFF, 0xFF, 0xFF, 0xFD]), Some(0xFFFF));
+    }
+
+    #[test]
+    fn bad_checksum() {
+        assert_eq!(parse_frame(&[0xFF, 0x07, 0xA1, 0xA6]), None);
+        // The first frame is corrupt, the next one is used
+        assert_eq!(
+            parse_frame(&[0xFF, 0x07, 0xA1, 0x00, 0xFF, 0x07, 0xA0, 0xA6]),
+            Some(1952)
+        );
+    }
+
+    #[test]
+    fn bad_header() {
+        assert_eq!(parse_frame(&[0xFE, 0x07, 0xA1, 0xA7]), None);
+        assert_eq!(parse_frame(&[0x07, 0xA1, 0xA7]), None);
+        assert_eq!(parse_frame(&[]), None);
+    }
+
+    #[test]
+    fn out_of_range() {
+        assert_eq!(check_distance(29, 0, 1000), SensorFault::OutOfRange);
+        assert_eq!(check_distance(30, 0, 1000), SensorFault::None);
+        assert_eq!(check_distance(1000, 0, 1000), SensorFault::None);
+        assert_eq!(check_distance(1001, 0, 1000), SensorFault::OutOfRange);
+        // The offset counts towards the liquid height
+        assert_eq!(check_distance(960, 50, 1000), SensorFault::OutOfRange);
+        assert_eq!(check_distance(1040, -50, 1000), SensorFault::None);
+        assert_eq!(check_distance(i16::MAX, 0, 1000), SensorFault::OutOfRange);
+    }
+}
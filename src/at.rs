@@ -1,24 +1,125 @@
+//! Typed AT commands.  The responses are parsed by `propane_monitor_core::at`, which is tested on
+//! the host, so an unexpected response from the modem is an `Error` instead of a panic.  The
+//! async functions here send the command and parse the response.
 use crate::Error;
-use at_commands::parser::CommandParser;
-
-/// Parse AT+CESQ command response and return a signal strength in dBm
-/// Signal strength = -140 dBm + last int_parameter
-pub async fn get_signal_strength() -> Result<i32, Error> {
-    let command = nrf_modem::send_at::<32>("AT+CESQ").await?;
-
-    let (_, _, _, _, _, mut signal) = CommandParser::parse(command.as_bytes())
-        .expect_identifier(b"+CESQ:")
-        .expect_int_parameter()
-        .expect_int_parameter()
-        .expect_int_parameter()
-        .expect_int_parameter()
-        .expect_int_parameter()
-        .expect_int_parameter()
-        .expect_identifier(b"\r\n")
-        .finish()
-        .unwrap();
-    if signal != 255 {
-        signal += -140;
-    }
-    Ok(signal)
+use heapless::String;
+use propane_monitor_core::at::{parse_iccid, parse_imei, parse_snr, parse_temperature};
+pub use propane_monitor_core::at::{
+    Cereg, Cesq, Cmng, Cpsms, CredentialType, Edrx, FunctionalMode, GprsTimer, Ncellmeas,
+    NeighborCell, NetworkTime, RegistrationStatus, ServingCell, XMonitor, ICCID_LEN, IMEI_LEN,
+    MAX_NEIGHBORS, NCELLMEAS_MAX_LEN,
+};
+
+/// Read the extended signal quality, AT+CESQ
+pub async fn get_cesq() -> Result<Cesq, Error> {
+    let response = nrf_modem::send_at::<32>("AT+CESQ").await?;
+    Ok(Cesq::parse(response.as_str())?)
+}
+
+/// Return the signal strength (RSRP) in dBm from AT+CESQ, `None` when unknown
+pub async fn get_signal_strength() -> Result<Option<i32>, Error> {
+    Ok(get_cesq().await?.rsrp_dbm())
+}
+
+/// Read the signal to noise ratio (dB) with AT%XSNRSQ?, `None` when unknown
+pub async fn get_snr() -> Result<Option<i32>, Error> {
+    let response = nrf_modem::send_at::<32>("AT%XSNRSQ?").await?;
+    Ok(parse_snr(response.as_str())?)
+}
+
+/// Read the modem die temperature in °C with AT%XTEMP?
+pub async fn get_temperature() -> Result<i32, Error> {
+    let response = nrf_modem::send_at::<32>("AT%XTEMP?").await?;
+    Ok(parse_temperature(response.as_str())?)
+}
+
+/// Read the network registration status, AT+CEREG?
+pub async fn get_cereg() -> Result<Cereg, Error> {
+    let response = nrf_modem::send_at::<128>("AT+CEREG?").await?;
+    Ok(Cereg::parse(response.as_str())?)
+}
+
+/// Return whether the modem is registered to the network, either home or roaming
+pub async fn is_registered() -> Result<bool, Error> {
+    Ok(get_cereg().await?.stat.is_registered())
+}
+
+/// Return the network granted PSM active time and periodic TAU timer values (GPRS timer
+/// values), `None` when the network did not send them
+pub async fn get_psm_timers() -> Result<(Option<u8>, Option<u8>), Error> {
+    let cereg = get_cereg().await?;
+    Ok((cereg.active_time, cereg.periodic_tau))
+}
+
+/// Read the modem IMEI with AT+CGSN=1
+pub async fn get_imei() -> Result<String<IMEI_LEN>, Error> {
+    let response = nrf_modem::send_at::<64>("AT+CGSN=1").await?;
+    Ok(parse_imei(response.as_str())?)
+}
+
+/// Read the ICCID of the active SIM with AT%XICCID
+pub async fn get_iccid() -> Result<String<ICCID_LEN>, Error> {
+    let response = nrf_modem::send_at::<64>("AT%XICCID").await?;
+    Ok(parse_iccid(response.as_str())?)
+}
+
+/// Read the network time, AT+CCLK?
+pub async fn get_network_time() -> Result<NetworkTime, Error> {
+    let response = nrf_modem::send_at::<64>("AT+CCLK?").await?;
+    Ok(NetworkTime::parse(response.as_str())?)
+}
+
+/// Read the modem and serving cell status, AT%XMONITOR
+pub async fn get_xmonitor() -> Result<XMonitor, Error> {
+    let response = nrf_modem::send_at::<256>("AT%XMONITOR").await?;
+    Ok(XMonitor::parse(response.as_str())?)
+}
+
+/// Read the PSM setting, AT+CPSMS?
+pub async fn get_cpsms() -> Result<Cpsms, Error> {
+    let response = nrf_modem::send_at::<64>("AT+CPSMS?").await?;
+    Ok(Cpsms::parse(response.as_str())?)
+}
+
+/// Request PSM with the periodic TAU and active time GPRS timer values, or disable it with `None`
+pub async fn set_psm(timers: Option<(u8, u8)>) -> Result<(), Error> {
+    nrf_modem::send_at::<32>(Cpsms::command(timers).as_str()).await?;
+    Ok(())
+}
+
+/// Read the modem functional mode, AT+CFUN?
+pub async fn get_functional_mode() -> Result<FunctionalMode, Error> {
+    let response = nrf_modem::send_at::<32>("AT+CFUN?").await?;
+    Ok(FunctionalMode::parse(response.as_str())?)
+}
+
+/// Set the modem functional mode
+pub async fn set_functional_mode(mode: FunctionalMode) -> Result<(), Error> {
+    nrf_modem::send_at::<32>(mode.command().as_str()).await?;
+    Ok(())
+}
+
+/// Return whether a key or certificate is stored in the modem
+pub async fn has_credential(tag: u32, ty: CredentialType) -> Result<bool, Error> {
+    let response = nrf_modem::send_at::<128>(Cmng::list_command(tag, ty).as_str()).await?;
+    Ok(Cmng::parse(response.as_str())?.is_some())
+}
+
+/// Delete a key or certificate from the modem
+pub async fn delete_credential(tag: u32, ty: CredentialType) -> Result<(), Error> {
+    nrf_modem::send_at::<32>(Cmng::delete_command(tag, ty).as_str()).await?;
+    Ok(())
+}
+
+/// Write a key or certificate to the modem, see `Cmng::write_command` for the limits on `data`
+pub async fn write_credential(tag: u32, ty: CredentialType, data: &str) -> Result<(), Error> {
+    let cmd = Cmng::write_command(tag, ty, data)?;
+    nrf_modem::send_at::<32>(cmd.as_str()).await?;
+    Ok(())
+}
+
+/// Read the eDRX values in use with AT+CEDRXRDP
+pub async fn get_edrx() -> Result<Edrx, Error> {
+    let response = nrf_modem::send_at::<64>("AT+CEDRXRDP").await?;
+    Ok(Edrx::parse(response.as_str())?)
 }
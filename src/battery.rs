@@ -0,0 +1,123 @@
+use crate::board::BATTERY;
+use crate::config::LOW_BATTERY_SOC;
+use defmt::Format;
+
+/// Supported battery chemistries
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Format)]
+pub enum Chemistry {
+    /// Lithium thionyl chloride primary cell (3.6 V nominal)
+    LiSoCl2,
+    /// Lithium polymer rechargeable cell (3.7 V nominal)
+    LiPo,
+}
+
+impl Chemistry {
+    /// Discharge curve as (mV, %) points, highest voltage first.  The voltages are those seen
+    /// while the radio is transmitting, as that is what decides when the device browns out.
+    fn curve(&self) -> &'static [(u32, u8)] {
+        match self {
+            Chemistry::LiSoCl2 => &[
+                (3600, 100),
+                (3550, 90),
+                (3500, 70),
+                (3450, 50),
+                (3400, 30),
+                (3300, 15),
+                (3200, 8),
+                (3000, 2),
+                (2700, 0),
+            ],
+            Chemistry::LiPo => &[
+                (4150, 100),
+                (4050, 90),
+                (3950, 79),
+                (3850, 65),
+                (3750, 50),
+                (3700, 40),
+                (3650, 30),
+                (3600, 20),
+                (3550, 12),
+                (3450, 5),
+                (3250, 0),
+            ],
+        }
+    }
+
+    /// Typical voltage sag while transmitting, used until a loaded measurement is available
+    fn typical_sag(&self) -> u32 {
+        match self {
+            Chemistry::LiSoCl2 => 100,
+            Chemistry::LiPo => 50,
+        }
+    }
+
+    /// Interpolate the state of charge (%) from a loaded battery voltage
+    pub fn soc(&self, mv: u32) -> u8 {
+        let curve = self.curve();
+        let (max_mv, max_soc) = curve[0];
+        if mv >= max_mv {
+            return max_soc;
+        }
+
+        for pair in curve.windows(2) {
+            let (hi_mv, hi_soc) = pair[0];
+            let (lo_mv, lo_soc) = pair[1];
+            if mv >= lo_mv {
+                let span = (hi_soc - lo_soc) as u32 * (mv - lo_mv) / (hi_mv - lo_mv);
+                return lo_soc + span as u8;
+            }
+        }
+        0
+    }
+}
+
+/// Battery state of charge estimation.  Measurements at rest are compensated by the voltage sag
+/// last measured right after a radio transmission.
+pub struct BatteryMonitor {
+    chemistry: Chemistry,
+    rest_mv: u32,
+    sag_mv: u32,
+}
+
+impl BatteryMonitor {
+    pub fn new() -> Self {
+        BatteryMonitor {
+            chemistry: BATTERY,
+            rest_mv: 0,
+            sag_mv: BATTERY.typical_sag(),
+        }
+    }
+
+    /// Record a battery measurement taken with the radio idle
+    pub fn update_rest(&mut self, mv: u32) {
+        self.rest_mv = mv;
+    }
+
+    /// Record a battery measurement taken during or right after a radio transmission
+    pub fn update_load(&mut self, mv: u32) {
+        if self.rest_mv != 0 {
+            self.sag_mv = self.rest_mv.saturating_sub(mv);
+        }
+    }
+
+    /// Battery voltage compensated for the transmit load
+    pub fn compensated_mv(&self) -> u32 {
+        self.rest_mv.saturating_sub(self.sag_mv)
+    }
+
+    /// Estimated state of charge (%)
+    pub fn soc(&self) -> u8 {
+        self.chemistry.soc(self.compensated_mv())
+    }
+
+    /// Battery is low and should be replaced (or charged)
+    pub fn is_low(&self) -> bool {
+        self.soc() <= LOW_BATTERY_SOC
+    }
+}
+
+impl Default for BatteryMonitor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
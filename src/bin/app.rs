@@ -4,19 +4,48 @@
 
 use defmt::{error, info, unwrap};
 use embassy_executor::Spawner;
-use embassy_nrf::gpio::{Flex, Level, Output, OutputDrive};
 use embassy_nrf::interrupt::{self, InterruptExt, Priority};
 use embassy_nrf::pac::{UARTE0, UARTE1};
 // use embassy_nrf::pwm::{Prescaler, SimplePwm};
-use embassy_nrf::saadc::{ChannelConfig, Config, Saadc};
-use embassy_time::{with_timeout, Duration, Ticker, Timer};
-use futures::StreamExt;
+use embassy_nrf::saadc::{Config, Saadc};
+use embassy_time::{with_timeout, Duration, Instant, Timer};
 use nrf_modem::{ConnectionPreference, SystemMode};
+use propane_monitor_embassy::battery::BatteryMonitor;
+use propane_monitor_embassy::board::{Board, ADC_CHANNELS, VBAT_CH};
+use propane_monitor_embassy::board_pins;
+#[cfg(feature = "deep-sleep")]
+use propane_monitor_embassy::deep_sleep::{self, RetainedState};
+use propane_monitor_embassy::edrx::request_edrx;
+use propane_monitor_embassy::energy::{self, EnergyState};
+use propane_monitor_embassy::events::BatteryCritical;
+#[cfg(feature = "modbus")]
+use propane_monitor_embassy::modbus::ModbusMaster;
+use propane_monitor_embassy::periodic::PeriodicUplinks;
+use propane_monitor_embassy::power::{system_off, ModemPower, PowerMode, PowerPolicy};
 use propane_monitor_embassy::psk::install_psk_id_and_psk;
+use propane_monitor_embassy::psm::PsmManager;
+use propane_monitor_embassy::rai;
+use propane_monitor_embassy::registration::{self, RegistrationTracker};
+use propane_monitor_embassy::sensor::{measure, tank_sensors, SaadcAdc, TimerDelay};
+use propane_monitor_embassy::status::{self, set_status, LedStatus, StatusLed};
+use propane_monitor_embassy::tank::tank_monitors;
+#[cfg(feature = "ultrasonic")]
+use propane_monitor_embassy::ultrasonic::{self, UltrasonicUart};
 use propane_monitor_embassy::*;
+#[cfg(feature = "board-icarus")]
+use propane_monitor_embassy::{board::VIN_CH, charger::Charger, sim::SimManager};
+#[cfg(feature = "tamper")]
+use {
+    embassy_futures::select::{select, Either},
+    embassy_nrf::twim::{self, Twim},
+    propane_monitor_embassy::events::TamperAlert,
+    propane_monitor_embassy::tamper::{Accelerometer, TamperDetector},
+};
+#[cfg(not(feature = "deep-sleep"))]
+use {embassy_time::Ticker, futures::StreamExt};
 
 #[embassy_executor::main]
-async fn main(_spawner: Spawner) {
+async fn main(spawner: Spawner) {
     // Set up the interrupts for the modem
     let egu1 = interrupt::take!(EGU1);
     egu1.set_priority(Priority::P4);
@@ -44,7 +73,7 @@ async fn main(_spawner: Spawner) {
     alloc_init();
 
     // Run our sampling program, will not return unless an error occurs
-    match run().await {
+    match run(spawner).await {
         Ok(()) => unreachable!(),
         Err(e) => {
             // If we get here, we have problems
@@ -54,50 +83,62 @@ async fn main(_spawner: Spawner) {
     }
 }
 
-async fn run() -> Result<(), Error> {
+/// Show the device status on the LED until the LED timeout
+#[embassy_executor::task]
+async fn status_led(led: StatusLed) {
+    led.run().await
+}
+
+/// Keep the latest ultrasonic sensor reading while the sensor is powered
+#[cfg(feature = "ultrasonic")]
+#[embassy_executor::task]
+async fn ultrasonic_rx(uart: UltrasonicUart) {
+    ultrasonic::receive(uart).await
+}
+
+/// Follow the network registration from the +CEREG notifications
+#[embassy_executor::task]
+async fn track_registration(tracker: RegistrationTracker) {
+    tracker.run().await
+}
+
+async fn run(spawner: Spawner) -> Result<(), Error> {
     // Handle for device peripherals
-    let mut p = embassy_nrf::init(Default::default());
+    let p = embassy_nrf::init(Default::default());
+
+    // Board specific pins: sensors, battery measurement, LEDs, SIM select and charging control
+    let (mut board, sensor_pins) = Board::new(board_pins!(p));
+    let (channels, sensor_power) = sensor_pins.split();
+
+    // Pick up the batch after a wake up from System OFF, setup that survives it is skipped
+    #[cfg(feature = "deep-sleep")]
+    let restored = deep_sleep::restore();
+    #[cfg(feature = "deep-sleep")]
+    let cold_boot = restored.is_none();
+    #[cfg(not(feature = "deep-sleep"))]
+    let cold_boot = true;
 
-    // Stratus: Disconnect accelerometer for power savings
-    Flex::new(&mut p.P0_29).set_as_disconnected();
+    // Status LED shows booting until told otherwise, only after power up
+    if cold_boot {
+        let led = StatusLed::new(
+            p.PWM0,
+            board.led_red.take(),
+            board.led_green.take(),
+            unwrap!(board.led_blue.take()),
+        );
+        unwrap!(spawner.spawn(status_led(led)));
+    }
 
     // Configuration of ADC, over sample to reduce noise (8x)
     let adc_config = Config::default();
     // Oversample can only be used when you have a single channel
     // adc_config.oversample = Oversample::OVER8X;
 
-    // Pin 14 can be used on both Stratus and Icarus boards for Analog Input
-    let sensor_channel = ChannelConfig::single_ended(&mut p.P0_14);
-    // Stratus: Pin 20 for V_bat measurement
-    // Icarus: Pin 13 for V_bat measurement
-    let bat_channel = ChannelConfig::single_ended(&mut p.P0_20);
-
-    let mut adc = Saadc::new(
-        p.SAADC,
-        interrupt::take!(SAADC),
-        adc_config,
-        // [sensor_channel],
-        [sensor_channel, bat_channel],
-    );
+    let mut adc = Saadc::new(p.SAADC, interrupt::take!(SAADC), adc_config, channels);
+    set_status(LedStatus::Calibration);
     adc.calibrate().await;
     info!("ADC Initialized");
 
-    // Icarus: Has an eSIM and an External SIM.  Use Pin 8 to select: HIGH = eSIM, Low = External
-    // Only change SIM selection while modem is off (AT+CFUN=1)
-    // let _sim_select = Output::new(p.P0_08, Level::Low, OutputDrive::Standard);
-
-    // Hall effect sensor power, must be High Drive to provide enough current (6 mA)
-    let mut hall_effect = Output::new(p.P0_31, Level::Low, OutputDrive::Disconnect0HighDrive1);
-
-    // Stratus: Pin 25 to control VBAT_MEAS_EN, Power must connect to V_Bat to measure correctly
-    // Icarus: Pin 07 to disable battery charging circuit
-    let mut enable_bat_meas = Output::new(p.P0_25, Level::Low, OutputDrive::Standard);
-    // let _disable_charging = Output::new(p.P0_07, Level::High, OutputDrive::Standard);
-
-    // Stratus: Pin 3 for blue LED power when data is being transmitted
-    // Stratus: Pin 12 for blue LED power when data is being transmitted, (red: P_10, green: P_11)
-    let mut led = Output::new(p.P0_03, Level::High, OutputDrive::Standard);
-
     // Initialize cellular modem
     unwrap!(
         nrf_modem::init(SystemMode {
@@ -110,80 +151,331 @@ async fn run() -> Result<(), Error> {
         .await
     );
 
+    // Release assistance is only accepted while the modem is still offline after init
+    if let Err(e) = rai::enable().await {
+        error!("RAI not enabled: {:?}", defmt::Debug2Format(&e));
+    }
+
+    // Uplinks wait for registration, tracked from the +CEREG notifications
+    unwrap!(spawner.spawn(track_registration(RegistrationTracker::new().await?)));
+
     // Configure GPS settings
     // config_gnss().await?;
 
-    // install PSK info for secure cloud connectivity
-    install_psk_id_and_psk().await?;
+    // install PSK info for secure cloud connectivity, the modem keeps it through System OFF
+    if cold_boot {
+        install_psk_id_and_psk().await?;
+    }
+
+    // Ask the network for PSM so the modem can sleep between uplinks, or eDRX to stay reachable
+    let modem_power = ModemPower::configured();
+    let mut psm = PsmManager::new();
+    match modem_power {
+        ModemPower::Psm => psm.request().await?,
+        ModemPower::Edrx => request_edrx().await?,
+    }
+    let mut periodic = PeriodicUplinks::new();
 
     // Heapless buffer to hold our sample values before transmitting
     let mut payload = Payload::new();
 
-    // Create our sleep timer (time between sensor measurements)
-    let mut ticker = Ticker::every(Duration::from_secs(3));
-    info!("Entering Loop");
-    loop {
-        let mut timeout = 30;
-        if payload.message == 0 {
-            timeout = 1800;
+    // Ultrasonic sensor UART, only enabled while measuring
+    #[cfg(feature = "ultrasonic")]
+    unwrap!(spawner.spawn(ultrasonic_rx(UltrasonicUart::new(
+        p.UARTETWISPI0,
+        interrupt::take!(UARTE0_SPIM0_SPIS0_TWIM0_TWIS0),
+        unwrap!(board.uart_rx.take()),
+    ))));
+
+    // Modbus RTU devices polled before each uplink
+    #[cfg(feature = "modbus")]
+    let mut modbus = ModbusMaster::new(
+        p.UARTETWISPI1,
+        interrupt::take!(UARTE1_SPIM1_SPIS1_TWIM1_TWIS1),
+        unwrap!(board.rs485_rx.take()),
+        unwrap!(board.rs485_tx.take()),
+        unwrap!(board.rs485_de.take()),
+    );
+
+    // Sensor drivers along with fault, refill and leak detection for each tank
+    let mut sensors = tank_sensors(sensor_power);
+    let mut tanks = tank_monitors();
+
+    // Battery state of charge estimation, compensated with a measurement after each transmission
+    let mut battery = BatteryMonitor::new();
+
+    // Sampling interval, batch size and optional features follow the battery voltage
+    let mut power = PowerPolicy::new();
+    let mut profile = psm.align(power.mode().profile());
+
+    // Icarus: SIM selection, falls back to the other SIM when the selected one fails
+    #[cfg(feature = "board-icarus")]
+    let mut sim = SimManager::new();
+
+    // Continue from the state saved before System OFF
+    #[cfg(feature = "deep-sleep")]
+    let mut clock = 0;
+    #[cfg(feature = "deep-sleep")]
+    if let Some(state) = restored {
+        payload = state.payload;
+        tanks = state.tanks;
+        battery = state.battery;
+        power = state.power;
+        periodic = state.periodic;
+        clock = state.clock;
+        energy::restore(state.energy);
+        registration::restore_attached(state.attached);
+        profile = psm.align(power.mode().profile());
+        // The select pin was reset with the device, the modem is still offline
+        #[cfg(feature = "board-icarus")]
+        {
+            sim = state.sim;
+            board.select_sim(sim.active());
         }
-        let mut buf = [0; 2];
+    }
+
+    // Icarus: register with the preferred SIM, or fall back to the other one.  Only after power
+    // up, the SIM found then is kept through System OFF
+    #[cfg(feature = "board-icarus")]
+    if cold_boot {
+        match sim.start(&mut board).await {
+            Ok(status) => payload.sim = Some(status),
+            Err(e) => error!("SIM selection failed: {:?}", defmt::Debug2Format(&e)),
+        }
+    }
 
-        // get_gnss_data().await?;
+    // Charging is only allowed inside the safe temperature range
+    #[cfg(feature = "board-icarus")]
+    let mut charger = Charger::new();
 
-        // Power up the hall sensor: max power on time = 330us (wait for 500us to be safe)
-        hall_effect.set_high();
-        enable_bat_meas.set_high();
+    // Accelerometer wakes us on motion, the orientation at boot is the installed orientation
+    #[cfg(feature = "tamper")]
+    let (mut accel, mut tamper) = {
+        let twim = Twim::new(
+            p.UARTETWISPI2,
+            interrupt::take!(UARTE2_SPIM2_SPIS2_TWIM2_TWIS2),
+            unwrap!(board.accel_sda.take()),
+            unwrap!(board.accel_scl.take()),
+            twim::Config::default(),
+        );
+        let mut accel = Accelerometer::new(twim, unwrap!(board.accel_int.take())).await?;
+        let reference = accel.acceleration().await?;
+        info!("Installed orientation: {} mg", reference);
+        (accel, TamperDetector::new(reference))
+    };
 
+    // Create our sleep timer (time between sensor measurements)
+    #[cfg(not(feature = "deep-sleep"))]
+    let mut ticker = Ticker::every(Duration::from_secs(profile.sample_interval));
+    info!("Entering Loop");
+    loop {
+        // Until the first attach the uplinks wait for the registration far longer
+        let timeout = registration::uplink_timeout();
+        let mut buf = [0; ADC_CHANNELS];
+
+        // Power must connect to V_bat to measure correctly
+        energy::enter(EnergyState::Adc);
+        board.vbat_measurement(true);
         Timer::after(Duration::from_micros(500)).await;
         adc.sample(&mut buf).await;
+        board.vbat_measurement(false);
+        energy::enter(EnergyState::Sleep);
 
-        hall_effect.set_low();
-        enable_bat_meas.set_low();
+        let vbat = convert_to_mv(buf[VBAT_CH]);
+        battery.update_rest(vbat);
+        info!("Battery: {} mV", vbat);
 
-        info!(
-            "Tank level: {}%, Battery: {} mV",
-            convert_to_tank_level(buf[0]),
-            convert_to_mv(buf[buf.len() - 1])
-        );
+        // Charger and solar panel monitoring, Icarus only
+        #[cfg(feature = "board-icarus")]
+        match charger
+            .update(&mut board, convert_to_vin_mv(buf[VIN_CH]))
+            .await
+        {
+            Ok(status) => payload.charger = Some(status),
+            Err(e) => error!("Charger update failed: {:?}", defmt::Debug2Format(&e)),
+        }
 
-        payload
-            .data
-            .push(TankLevel::new(
-                convert_to_tank_level(buf[0]),
-                1987,
-                convert_to_mv(buf[buf.len() - 1]),
-            ))
-            .unwrap();
+        // Tank events are sent right away instead of waiting for the batch
+        let now = Instant::now().as_secs() as u32;
+        #[cfg(feature = "deep-sleep")]
+        let now = clock + now;
+        for (i, (tank, sensor)) in tanks.iter_mut().zip(sensors.iter_mut()).enumerate() {
+            energy::enter(EnergyState::Sensor);
+            let measurement =
+                measure(sensor.as_mut(), &mut SaadcAdc(&mut adc), &mut TimerDelay).await;
+            energy::enter(EnergyState::Sleep);
+            let (sample, events) = tank.update(measurement, vbat, now);
+            for event in events.iter() {
+                let sent = transmit_event(event, &mut payload.radio);
+                if let Ok(Ok(_)) = with_timeout(timeout, sent).await {
+                    info!("Tank {} event sent", tank.id);
+                } else {
+                    info!("Tank {} event could not be sent", tank.id);
+                }
+            }
+            payload.tanks[i].data.push(sample).unwrap();
+        }
 
         // Our payload data buff is full, send to the cloud, clear the buffer
-        if payload.data.is_full() {
+        if payload.samples() >= profile.batch_size {
             // info!("TankLevel: {}", core::mem::size_of::<TankLevel>());
             info!("Payload is full");
             payload.message += 1;
+            payload.soc = battery.soc();
+            payload.low_battery = battery.is_low();
+            info!("Battery: {}%, low: {}", payload.soc, payload.low_battery);
+
+            #[cfg(feature = "modbus")]
+            if profile.modbus {
+                payload.modbus = modbus.poll().await;
+            }
+
             // Visibly show that data is being sent
-            led.set_low();
+            set_status(LedStatus::Transmitting);
 
-            // If timeout occurs, log a timeout and continue.
-            if let Ok(_) =
-                with_timeout(Duration::from_secs(timeout), transmit_payload(&mut payload)).await
-            {
+            // If timeout or an error occurs (e.g. no registration), log it and continue.
+            let result = with_timeout(timeout, transmit_payload(&mut payload)).await;
+            if let Ok(Ok(_)) = result {
                 payload.timeouts = 0;
+                if payload.low_battery {
+                    set_status(LedStatus::LowBattery);
+                } else {
+                    registration::show_status();
+                }
 
                 info!("Transfer Complete");
             } else {
                 payload.timeouts += 1;
+                set_status(LedStatus::Error);
+                if let Ok(Err(e)) = result {
+                    error!("Uplink failed: {:?}", defmt::Debug2Format(&e));
+                }
                 info!(
                     "Timeout has occurred {} time(s), data clear and start over",
                     payload.timeouts
                 );
+
+                // Coverage varies between carriers, try the other SIM
+                #[cfg(feature = "board-icarus")]
+                if sim.needs_fallback(payload.timeouts) {
+                    match sim.fallback(&mut board).await {
+                        Ok(status) => payload.sim = Some(status),
+                        Err(e) => error!("SIM fallback failed: {:?}", defmt::Debug2Format(&e)),
+                    }
+                }
+            }
+
+            payload.clear();
+
+            // Follow the PSM timers granted by the network
+            if psm.update(payload.psm) {
+                profile = psm.align(power.mode().profile());
             }
 
-            payload.data.clear();
+            // Diagnostics with the negotiated modem settings, the serving cell information and the
+            // cells for the cell based location, after boot and then daily
+            if periodic.due(now, &profile) {
+                let sent =
+                    transmit_periodic(&mut periodic, &mut payload, modem_power, &profile, now);
+                if let Ok(Ok(_)) = with_timeout(timeout, sent).await {
+                    info!("Periodic uplinks sent");
+                } else {
+                    info!("Periodic uplinks could not all be sent");
+                }
+            }
 
-            led.set_high();
+            // Measure the battery right after transmitting to see how far it sags under load
+            energy::enter(EnergyState::Adc);
+            board.vbat_measurement(true);
+            Timer::after(Duration::from_micros(500)).await;
+            adc.sample(&mut buf).await;
+            board.vbat_measurement(false);
+            energy::enter(EnergyState::Sleep);
+            battery.update_load(convert_to_mv(buf[VBAT_CH]));
+            info!("Energy used: {} mAh", energy::report().used_mah);
         }
+        // Follow the battery voltage down through the power modes
+        match power.update(battery.compensated_mv()) {
+            Some(PowerMode::Critical) => {
+                error!("Battery critical, shutting down");
+                let event = BatteryCritical::new(battery.compensated_mv(), battery.soc(), now);
+                let sent = transmit_event(&event, &mut payload.radio);
+                if let Ok(Ok(_)) = with_timeout(timeout, sent).await {
+                    info!("Battery critical event sent");
+                }
+                system_off().await;
+            }
+            Some(mode) => {
+                profile = psm.align(mode.profile());
+                if !profile.status_led {
+                    status::turn_off();
+                }
+                #[cfg(not(feature = "deep-sleep"))]
+                {
+                    ticker = Ticker::every(Duration::from_secs(profile.sample_interval));
+                }
+            }
+            None => {}
+        }
+
+        // Sleep in System OFF until the external timer wakes us up
+        #[cfg(feature = "deep-sleep")]
+        deep_sleep::sleep(
+            RetainedState {
+                payload,
+                tanks,
+                battery,
+                power,
+                periodic,
+                #[cfg(feature = "board-icarus")]
+                sim,
+                energy: energy::snapshot(),
+                attached: registration::attached(),
+                clock: clock + Instant::now().as_secs() as u32,
+            },
+            unwrap!(board.timer_wake.take()),
+        )
+        .await;
+
+        #[cfg(not(feature = "deep-sleep"))]
         info!("Ticker next()");
+        #[cfg(not(any(feature = "tamper", feature = "deep-sleep")))]
         ticker.next().await; // wait for next tick event
+
+        // Wait for the next tick event, handling motion in the meantime.  Tamper detection is
+        // off while saving power
+        #[cfg(feature = "tamper")]
+        if profile.tamper {
+            loop {
+                if let Either::First(_) = select(ticker.next(), accel.wait_for_motion()).await {
+                    break;
+                }
+
+                match accel.motion().await {
+                    Ok(reading) => {
+                        if let Some(kind) = tamper.update(reading) {
+                            error!("Tamper detected: {}, {} mg", kind, reading);
+                            let now = Instant::now().as_secs() as u32;
+                            let alert = TamperAlert::new(kind, reading, now);
+                            let sent = transmit_event(&alert, &mut payload.radio);
+                            if let Ok(Ok(_)) = with_timeout(timeout, sent).await {
+                                info!("Tamper alert sent");
+                            } else {
+                                info!("Tamper alert could not be sent");
+                            }
+                        }
+                    }
+                    Err(e) => {
+                        // Interrupt may still be latched, sample as usual and retry on the next
+                        // tick
+                        error!("Accelerometer read failed: {:?}", defmt::Debug2Format(&e));
+                        ticker.next().await;
+                        break;
+                    }
+                }
+            }
+        } else {
+            ticker.next().await;
+        }
     }
 }
@@ -4,19 +4,32 @@
 
 use defmt::{error, info, unwrap};
 use embassy_executor::Spawner;
-use embassy_nrf::gpio::{Flex, Level, Output, OutputDrive};
+use embassy_nrf::gpio::{Level, Output, OutputDrive};
 use embassy_nrf::interrupt::{self, InterruptExt, Priority};
 use embassy_nrf::pac::{UARTE0, UARTE1};
 use embassy_nrf::pwm::{Prescaler, SimplePwm};
-use embassy_nrf::saadc::{ChannelConfig, Config, Saadc};
+use embassy_nrf::saadc::{Config, Saadc};
 use embassy_time::{with_timeout, Duration, Ticker, Timer};
 use futures::StreamExt;
 use nrf_modem::{ConnectionPreference, SystemMode};
+use propane_monitor_embassy::board::{Board, ADC_CHANNELS, VBAT_CH};
+use propane_monitor_embassy::board_pins;
+use propane_monitor_embassy::power::PowerMode;
 use propane_monitor_embassy::psk::install_psk_id_and_psk;
+use propane_monitor_embassy::sensor::{measure, tank_sensors, SaadcAdc, TimerDelay};
+#[cfg(feature = "ultrasonic")]
+use propane_monitor_embassy::ultrasonic::{self, UltrasonicUart};
 use propane_monitor_embassy::*;
 
+/// Keep the latest ultrasonic sensor reading while the sensor is powered
+#[cfg(feature = "ultrasonic")]
+#[embassy_executor::task]
+async fn ultrasonic_rx(uart: UltrasonicUart) {
+    ultrasonic::receive(uart).await
+}
+
 #[embassy_executor::main]
-async fn main(_spawner: Spawner) {
+async fn main(spawner: Spawner) {
     // Set up the interrupts for the modem
     let egu1 = interrupt::take!(EGU1);
     egu1.set_priority(Priority::P4);
@@ -44,7 +57,7 @@ async fn main(_spawner: Spawner) {
     alloc_init();
 
     // Run our sampling program, will not return unless an error occurs
-    match run().await {
+    match run(spawner).await {
         Ok(()) => unreachable!(),
         Err(e) => {
             // If we get here, we have problems
@@ -54,15 +67,37 @@ async fn main(_spawner: Spawner) {
     }
 }
 
-async fn run() -> Result<(), Error> {
+async fn run(
+    #[cfg_attr(not(feature = "ultrasonic"), allow(unused_variables))] spawner: Spawner,
+) -> Result<(), Error> {
     // Handle for device peripherals
-    let mut p = embassy_nrf::init(Default::default());
-
-    // Stratus: Disconnect accelerometer for power savings
-    Flex::new(&mut p.P0_29).set_as_disconnected();
-
-    // Demo PWM servo control
-    let mut pwm = SimplePwm::new_1ch(p.PWM0, p.P0_10);
+    let p = embassy_nrf::init(Default::default());
+
+    // Board specific pins: sensors, battery measurement, LEDs, SIM select and charging control
+    let (mut board, sensor_pins) = Board::new(board_pins!(p));
+    let (channels, sensor_power) = sensor_pins.split();
+    // Ultrasonic sensor UART, only enabled while measuring
+    #[cfg(feature = "ultrasonic")]
+    unwrap!(spawner.spawn(ultrasonic_rx(UltrasonicUart::new(
+        p.UARTETWISPI0,
+        interrupt::take!(UARTE0_SPIM0_SPIS0_TWIM0_TWIS0),
+        unwrap!(board.uart_rx.take()),
+    ))));
+    let mut sensors = tank_sensors(sensor_power);
+
+    // Demo PWM servo control on P0_10, which is the red LED on Icarus
+    #[cfg(feature = "board-stratus")]
+    let servo = propane_monitor_embassy::board::pin(p.P0_10);
+    #[cfg(feature = "board-icarus")]
+    let servo = unwrap!(board.led_red.take());
+    let mut pwm = SimplePwm::new_1ch(p.PWM0, servo);
+
+    // Blue LED shows when data is being sent, active low
+    let mut led = Output::new(
+        unwrap!(board.led_blue.take()),
+        Level::High,
+        OutputDrive::Standard,
+    );
     pwm.set_prescaler(Prescaler::Div128);
     pwm.set_max_duty(2500);
     info!("pwm initialized!");
@@ -72,38 +107,10 @@ async fn run() -> Result<(), Error> {
     // Oversample can only be used when you have a single channel
     // adc_config.oversample = Oversample::OVER8X;
 
-    // Pin 14 can be used on both Stratus and Icarus boards for Analog Input
-    let sensor_channel = ChannelConfig::single_ended(&mut p.P0_14);
-    // Stratus: Pin 20 for V_bat measurement
-    // Icarus: Pin 13 for V_bat measurement
-    let bat_channel = ChannelConfig::single_ended(&mut p.P0_20);
-
-    let mut adc = Saadc::new(
-        p.SAADC,
-        interrupt::take!(SAADC),
-        adc_config,
-        // [sensor_channel],
-        [sensor_channel, bat_channel],
-    );
+    let mut adc = Saadc::new(p.SAADC, interrupt::take!(SAADC), adc_config, channels);
     adc.calibrate().await;
     info!("ADC Initialized");
 
-    // Icarus: Has an eSIM and an External SIM.  Use Pin 8 to select: HIGH = eSIM, Low = External
-    // Only change SIM selection while modem is off (AT+CFUN=1)
-    // let _sim_select = Output::new(p.P0_08, Level::Low, OutputDrive::Standard);
-
-    // Hall effect sensor power, must be High Drive to provide enough current (6 mA)
-    let mut hall_effect = Output::new(p.P0_31, Level::Low, OutputDrive::Disconnect0HighDrive1);
-
-    // Stratus: Pin 25 to control VBAT_MEAS_EN, Power must connect to V_Bat to measure correctly
-    // Icarus: Pin 07 to disable battery charging circuit
-    let mut enable_bat_meas = Output::new(p.P0_25, Level::Low, OutputDrive::Standard);
-    // let _disable_charging = Output::new(p.P0_07, Level::High, OutputDrive::Standard);
-
-    // Stratus: Pin 3 for blue LED power when data is being transmitted
-    // Stratus: Pin 12 for blue LED power when data is being transmitted, (red: P_10, green: P_11)
-    let mut led = Output::new(p.P0_03, Level::High, OutputDrive::Standard);
-
     // Initialize cellular modem
     unwrap!(
         nrf_modem::init(SystemMode {
@@ -146,37 +153,38 @@ async fn run() -> Result<(), Error> {
             pwm.set_duty(0, 2500 - *duty);
             Timer::after(Duration::from_millis(500)).await;
 
-            let mut buf = [0; 2];
+            let mut buf = [0; ADC_CHANNELS];
 
             // get_gnss_data().await?;
 
-            // Power up the hall sensor: max power on time = 330us (wait for 500us to be safe)
-            hall_effect.set_high();
-            enable_bat_meas.set_high();
-
+            // Power must connect to V_bat to measure correctly
+            board.vbat_measurement(true);
             Timer::after(Duration::from_micros(500)).await;
             adc.sample(&mut buf).await;
-
-            hall_effect.set_low();
-            enable_bat_meas.set_low();
-
-            info!(
-                "Tank level: {}%, Battery: {} mV",
-                convert_to_tank_level(buf[0]),
-                convert_to_mv(buf[buf.len() - 1])
-            );
-
-            payload
+            board.vbat_measurement(false);
+            let vbat = convert_to_mv(buf[VBAT_CH]);
+
+            let measurement = measure(
+                sensors[0].as_mut(),
+                &mut SaadcAdc(&mut adc),
+                &mut TimerDelay,
+            )
+            .await;
+            info!("Tank level: {}%, Battery: {} mV", measurement.level, vbat);
+
+            payload.tanks[0]
                 .data
                 .push(TankLevel::new(
-                    convert_to_tank_level(buf[0]),
+                    measurement.level,
+                    measurement.pressure,
                     1987,
-                    convert_to_mv(buf[buf.len() - 1]),
+                    vbat,
+                    measurement.fault,
                 ))
                 .unwrap();
 
             // Our payload data buff is full, send to the cloud, clear the buffer
-            if payload.data.is_full() {
+            if payload.samples() >= PowerMode::Normal.profile().batch_size {
                 // info!("TankLevel: {}", core::mem::size_of::<TankLevel>());
                 info!("Payload is full");
 
@@ -198,7 +206,7 @@ async fn run() -> Result<(), Error> {
                     );
                 }
 
-                payload.data.clear();
+                payload.clear();
 
                 led.set_high();
             }
@@ -0,0 +1,358 @@
+//! Board support for the Conexio Stratus and Actinius Icarus, selected with the `board-stratus`
+//! or `board-icarus` cargo feature.  Pins are taken from the embassy peripherals with the
+//! `board_pins!` macro and handed to `Board::new`.
+use crate::battery::Chemistry;
+use crate::config::{TANKS, TANK_COUNT};
+use crate::tank::SensorKind;
+use defmt::Format;
+use embassy_nrf::gpio::{AnyPin, Flex, Level, Output, OutputDrive, Pin};
+use embassy_nrf::gpio::{Input as GpioInput, Pull};
+use embassy_nrf::saadc::{AnyInput, ChannelConfig, Gain, Input, Reference, Resistor};
+use serde::Serialize;
+
+#[cfg(all(feature = "board-stratus", feature = "board-icarus"))]
+compile_error!("Only one of the `board-stratus` and `board-icarus` features can be enabled");
+
+#[cfg(not(any(feature = "board-stratus", feature = "board-icarus")))]
+compile_error!("One of the `board-stratus` or `board-icarus` features must be enabled");
+
+/// Battery measurement voltage divider multiplier (numerator, denominator)
+#[cfg(feature = "board-stratus")]
+pub const VBAT_DIVIDER: (u32, u32) = (200, 100);
+#[cfg(feature = "board-icarus")]
+pub const VBAT_DIVIDER: (u32, u32) = (147, 100);
+
+/// Charger input (solar panel) voltage divider multiplier (numerator, denominator)
+#[cfg(feature = "board-icarus")]
+pub const VIN_DIVIDER: (u32, u32) = (300, 100);
+
+/// Number of tank sensor channels (analog input and power pin pairs) on the board
+pub const SENSOR_CHANNELS: usize = 3;
+
+/// SAADC channel indices, see `SensorPins::split`.  The configured tanks come first, in the
+/// order of `config::TANKS`
+pub const VBAT_CH: usize = TANK_COUNT;
+#[cfg(feature = "board-icarus")]
+pub const VIN_CH: usize = TANK_COUNT + 1;
+
+/// Number of SAADC channels in use
+#[cfg(feature = "board-stratus")]
+pub const ADC_CHANNELS: usize = TANK_COUNT + 1;
+#[cfg(feature = "board-icarus")]
+pub const ADC_CHANNELS: usize = TANK_COUNT + 2;
+
+/// Battery chemistry fitted to the board
+#[cfg(feature = "board-stratus")]
+pub const BATTERY: Chemistry = Chemistry::LiSoCl2;
+#[cfg(feature = "board-icarus")]
+pub const BATTERY: Chemistry = Chemistry::LiPo;
+
+/// Raw board pins, create with the `board_pins!` macro
+pub struct BoardPins {
+    pub inputs: [AnyInput; SENSOR_CHANNELS],
+    pub vbat: AnyInput,
+    pub sensor_power: [AnyPin; SENSOR_CHANNELS],
+    pub vbat_enable: Option<AnyPin>,
+    pub accel_int: Option<AnyPin>,
+    pub accel_sda: Option<AnyPin>,
+    pub accel_scl: Option<AnyPin>,
+    pub led_red: Option<AnyPin>,
+    pub led_green: Option<AnyPin>,
+    pub led_blue: AnyPin,
+    pub sim_select: Option<AnyPin>,
+    pub charge_disable: Option<AnyPin>,
+    pub charge_status: Option<AnyPin>,
+    pub uart_rx: AnyPin,
+    pub timer_wake: AnyPin,
+    pub rs485_rx: AnyPin,
+    pub rs485_tx: AnyPin,
+    pub rs485_de: AnyPin,
+    #[cfg(feature = "board-icarus")]
+    pub vin: AnyInput,
+}
+
+/// Take the board pins out of the embassy peripherals
+/// Stratus: sensors P0_14/P0_15/P0_16 powered by P0_31/P0_24/P0_23, V_bat P0_20,
+/// VBAT_MEAS_EN P0_25, accelerometer INT1 P0_29 (I2C SDA P0_26, SCL P0_27), blue LED P0_03,
+/// UART RX (ultrasonic sensor) P0_05, RS-485 RX P0_00, TX P0_01, DE P0_02, wake up timer P0_04
+#[cfg(feature = "board-stratus")]
+#[macro_export]
+macro_rules! board_pins {
+    ($p:ident) => {
+        $crate::board::BoardPins {
+            inputs: [
+                $crate::board::input($p.P0_14),
+                $crate::board::input($p.P0_15),
+                $crate::board::input($p.P0_16),
+            ],
+            vbat: $crate::board::input($p.P0_20),
+            sensor_power: [
+                $crate::board::pin($p.P0_31),
+                $crate::board::pin($p.P0_24),
+                $crate::board::pin($p.P0_23),
+            ],
+            vbat_enable: Some($crate::board::pin($p.P0_25)),
+            accel_int: Some($crate::board::pin($p.P0_29)),
+            accel_sda: Some($crate::board::pin($p.P0_26)),
+            accel_scl: Some($crate::board::pin($p.P0_27)),
+            led_red: None,
+            led_green: None,
+            led_blue: $crate::board::pin($p.P0_03),
+            sim_select: None,
+            charge_disable: None,
+            charge_status: None,
+            uart_rx: $crate::board::pin($p.P0_05),
+            timer_wake: $crate::board::pin($p.P0_04),
+            rs485_rx: $crate::board::pin($p.P0_00),
+            rs485_tx: $crate::board::pin($p.P0_01),
+            rs485_de: $crate::board::pin($p.P0_02),
+        }
+    };
+}
+
+/// Take the board pins out of the embassy peripherals
+/// Icarus: sensors P0_14/P0_15/P0_16 powered by P0_31/P0_24/P0_23, V_bat P0_13,
+/// RGB LED P0_10/P0_11/P0_12, SIM select P0_08, charge disable P0_07, charger /CHG status P0_09, charger input (solar) voltage P0_19,
+/// UART RX (ultrasonic sensor) P0_05, RS-485 RX P0_00, TX P0_01, DE P0_02, wake up timer P0_04
+#[cfg(feature = "board-icarus")]
+#[macro_export]
+macro_rules! board_pins {
+    ($p:ident) => {
+        $crate::board::BoardPins {
+            inputs: [
+                $crate::board::input($p.P0_14),
+                $crate::board::input($p.P0_15),
+                $crate::board::input($p.P0_16),
+            ],
+            vbat: $crate::board::input($p.P0_13),
+            sensor_power: [
+                $crate::board::pin($p.P0_31),
+                $crate::board::pin($p.P0_24),
+                $crate::board::pin($p.P0_23),
+            ],
+            vbat_enable: None,
+            accel_int: None,
+            accel_sda: None,
+            accel_scl: None,
+            led_red: Some($crate::board::pin($p.P0_10)),
+            led_green: Some($crate::board::pin($p.P0_11)),
+            led_blue: $crate::board::pin($p.P0_12),
+            sim_select: Some($crate::board::pin($p.P0_08)),
+            charge_disable: Some($crate::board::pin($p.P0_07)),
+            charge_status: Some($crate::board::pin($p.P0_09)),
+            uart_rx: $crate::board::pin($p.P0_05),
+            timer_wake: $crate::board::pin($p.P0_04),
+            rs485_rx: $crate::board::pin($p.P0_00),
+            rs485_tx: $crate::board::pin($p.P0_01),
+            rs485_de: $crate::board::pin($p.P0_02),
+            vin: $crate::board::input($p.P0_19),
+        }
+    };
+}
+
+/// Type erase an analog input pin, used by `board_pins!`
+pub fn input(pin: impl Input) -> AnyInput {
+    pin.degrade_saadc()
+}
+
+/// Type erase a GPIO pin, used by `board_pins!`
+pub fn pin(pin: impl Pin) -> AnyPin {
+    pin.degrade()
+}
+
+/// Icarus SIM selection
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Format, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Sim {
+    Esim,
+    External,
+}
+
+impl Sim {
+    /// The SIM to fall back to
+    pub fn other(&self) -> Sim {
+        match self {
+            Sim::Esim => Sim::External,
+            Sim::External => Sim::Esim,
+        }
+    }
+}
+
+/// Sensor inputs and power pins, split off from the board for the SAADC and the sensor drivers
+pub struct SensorPins {
+    pub inputs: [AnyInput; SENSOR_CHANNELS],
+    pub power: [Output<'static, AnyPin>; SENSOR_CHANNELS],
+    pub vbat: AnyInput,
+    #[cfg(feature = "board-icarus")]
+    pub vin: AnyInput,
+}
+
+impl SensorPins {
+    /// Split into the SAADC channel configuration and the sensor power pins by channel.
+    /// The SAADC channels are the configured tanks in order, then `VBAT_CH` and `VIN_CH`.
+    /// Ratiometric sensors are sampled against VDD/4 with 1/4 gain, full scale is the supply.
+    /// Hall sensor inputs are pulled down so they read a known level while the sensor is off
+    #[allow(clippy::type_complexity)]
+    pub fn split(
+        self,
+    ) -> (
+        [ChannelConfig<'static>; ADC_CHANNELS],
+        [Option<Output<'static, AnyPin>>; SENSOR_CHANNELS],
+    ) {
+        let mut inputs = self.inputs.map(Some);
+        let mut inputs = TANKS
+            .iter()
+            .map(|tank| {
+                let input = inputs[tank.channel]
+                    .take()
+                    .expect("tank channel used twice");
+                (input, Some(tank.sensor))
+            })
+            .chain([
+                (self.vbat, None),
+                #[cfg(feature = "board-icarus")]
+                (self.vin, None),
+            ]);
+        let channels = core::array::from_fn(|_| {
+            let (input, sensor) = inputs.next().unwrap();
+            let mut config = ChannelConfig::single_ended(input);
+            match sensor {
+                Some(SensorKind::HallEffect(_)) => config.resistor = Resistor::PULLDOWN,
+                Some(sensor) if sensor.ratiometric() => {
+                    config.reference = Reference::VDD1_4;
+                    config.gain = Gain::GAIN1_4;
+                }
+                _ => {}
+            }
+            config
+        });
+
+        (channels, self.power.map(Some))
+    }
+}
+
+/// Board peripherals used by the application
+pub struct Board {
+    pub accel_int: Option<AnyPin>,
+    pub accel_sda: Option<AnyPin>,
+    pub accel_scl: Option<AnyPin>,
+    /// Status LEDs, active low.  Only the blue LED is fitted on the Stratus
+    pub led_red: Option<AnyPin>,
+    pub led_green: Option<AnyPin>,
+    pub led_blue: Option<AnyPin>,
+    pub uart_rx: Option<AnyPin>,
+    /// External wake up timer output, for deep sleep
+    pub timer_wake: Option<AnyPin>,
+    pub rs485_rx: Option<AnyPin>,
+    pub rs485_tx: Option<AnyPin>,
+    pub rs485_de: Option<AnyPin>,
+    vbat_enable: Option<Output<'static, AnyPin>>,
+    sim_select: Option<Output<'static, AnyPin>>,
+    charge_disable: Option<Output<'static, AnyPin>>,
+    charge_status: Option<GpioInput<'static, AnyPin>>,
+}
+
+impl Board {
+    pub fn new(pins: BoardPins) -> (Self, SensorPins) {
+        let BoardPins {
+            inputs,
+            vbat,
+            sensor_power,
+            vbat_enable,
+            mut accel_int,
+            accel_sda,
+            accel_scl,
+            led_red,
+            led_green,
+            led_blue,
+            sim_select,
+            charge_disable,
+            charge_status,
+            uart_rx,
+            timer_wake,
+            rs485_rx,
+            rs485_tx,
+            rs485_de,
+            #[cfg(feature = "board-icarus")]
+            vin,
+        } = pins;
+
+        // Stratus: Disconnect accelerometer for power savings, the tamper driver takes it over
+        if let Some(accel) = accel_int.as_mut() {
+            Flex::new(accel).set_as_disconnected();
+        }
+
+        let board = Board {
+            accel_int,
+            accel_sda,
+            accel_scl,
+            led_red,
+            led_green,
+            led_blue: Some(led_blue),
+            uart_rx: Some(uart_rx),
+            timer_wake: Some(timer_wake),
+            rs485_rx: Some(rs485_rx),
+            rs485_tx: Some(rs485_tx),
+            rs485_de: Some(rs485_de),
+            // Stratus: VBAT_MEAS_EN, Power must connect to V_Bat to measure correctly
+            vbat_enable: vbat_enable.map(|p| Output::new(p, Level::Low, OutputDrive::Standard)),
+            // Icarus: HIGH = eSIM, LOW = External
+            sim_select: sim_select.map(|p| Output::new(p, Level::Low, OutputDrive::Standard)),
+            // Icarus: HIGH = charging disabled, until the charger checked the temperature
+            charge_disable: charge_disable
+                .map(|p| Output::new(p, Level::High, OutputDrive::Standard)),
+            // Icarus: open drain /CHG output of the charger, LOW = charging
+            charge_status: charge_status.map(|p| GpioInput::new(p, Pull::Up)),
+        };
+
+        let sensors = SensorPins {
+            inputs,
+            // Hall effect sensor power, must be High Drive to provide enough current (6 mA)
+            power: sensor_power
+                .map(|p| Output::new(p, Level::Low, OutputDrive::Disconnect0HighDrive1)),
+            vbat,
+            #[cfg(feature = "board-icarus")]
+            vin,
+        };
+
+        (board, sensors)
+    }
+
+    /// Connect the battery measurement circuit, does nothing on boards where it is always connected
+    pub fn vbat_measurement(&mut self, on: bool) {
+        if let Some(enable) = self.vbat_enable.as_mut() {
+            if on {
+                enable.set_high();
+            } else {
+                enable.set_low();
+            }
+        }
+    }
+
+    /// Select the SIM, only change the SIM selection while the modem is off (AT+CFUN=0).
+    /// Does nothing on boards without a SIM selection
+    pub fn select_sim(&mut self, sim: Sim) {
+        if let Some(select) = self.sim_select.as_mut() {
+            match sim {
+                Sim::Esim => select.set_high(),
+                Sim::External => select.set_low(),
+            }
+        }
+    }
+
+    /// Enable or disable the battery charging circuit, does nothing on boards without a charger
+    pub fn charging(&mut self, enabled: bool) {
+        if let Some(disable) = self.charge_disable.as_mut() {
+            if enabled {
+                disable.set_low();
+            } else {
+                disable.set_high();
+            }
+        }
+    }
+
+    /// Charger reports it is charging, `None` on boards without a charger
+    pub fn is_charging(&self) -> Option<bool> {
+        self.charge_status.as_ref().map(|status| status.is_low())
+    }
+}
@@ -0,0 +1,75 @@
+use crate::at::get_temperature;
+use crate::board::Board;
+use crate::config::{CHARGE_MAX_TEMP, CHARGE_MIN_TEMP, CHARGE_TEMP_HYSTERESIS};
+use crate::Error;
+use defmt::{info, Format};
+use serde::Serialize;
+
+/// Battery charger state
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Format, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChargeState {
+    Charging,
+    NotCharging,
+    /// Charging disabled because the temperature is outside of the safe range
+    Disabled,
+}
+
+/// Charger status reported in the payload
+#[derive(Debug, Clone, Copy, Format, Serialize)]
+pub struct ChargeStatus {
+    pub state: ChargeState,
+    pub vin: u32,
+    /// Modem die temperature (°C), the battery temperature is not measured
+    pub temperature: i32,
+}
+
+/// Charger management, only enables charging inside the safe temperature range.  The board starts
+/// with charging disabled until the first temperature reading.  The temperature is the modem die
+/// temperature from %XTEMP, the closest to the battery there is
+pub struct Charger {
+    /// `None` until the first temperature reading
+    enabled: Option<bool>,
+}
+
+impl Charger {
+    pub fn new() -> Self {
+        Charger { enabled: None }
+    }
+
+    /// Check the temperature, enable or disable charging and return the charger status.
+    /// `vin` is the charger input (solar panel) voltage in mV
+    pub async fn update(&mut self, board: &mut Board, vin: u32) -> Result<ChargeStatus, Error> {
+        let temperature = get_temperature().await?;
+
+        let enabled = if self.enabled == Some(false) {
+            (CHARGE_MIN_TEMP + CHARGE_TEMP_HYSTERESIS..=CHARGE_MAX_TEMP - CHARGE_TEMP_HYSTERESIS)
+                .contains(&temperature)
+        } else {
+            (CHARGE_MIN_TEMP..=CHARGE_MAX_TEMP).contains(&temperature)
+        };
+        if Some(enabled) != self.enabled {
+            info!("Charging enabled: {} ({} C)", enabled, temperature);
+            self.enabled = Some(enabled);
+        }
+        board.charging(enabled);
+
+        let state = match (enabled, board.is_charging()) {
+            (false, _) => ChargeState::Disabled,
+            (true, Some(true)) => ChargeState::Charging,
+            (true, _) => ChargeState::NotCharging,
+        };
+
+        Ok(ChargeStatus {
+            state,
+            vin,
+            temperature,
+        })
+    }
+}
+
+impl Default for Charger {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -1,3 +1,16 @@
+#[cfg(feature = "board-icarus")]
+use crate::board::Sim;
+use crate::power::ModemPower;
+use crate::rai::ReleaseAssistance;
+use crate::tank::{SensorKind, SensorProfile, TankConfig};
+use propane_monitor_core::fault::FaultParams;
+use propane_monitor_core::leak::LeakParams;
+#[cfg(feature = "modbus")]
+use {
+    crate::modbus::{ModbusPoint, RegisterFormat, RegisterKind},
+    embassy_nrf::uarte::Baudrate,
+};
+
 pub const SERVER_URL: &str = "coap.golioth.io";
 pub const SERVER_PORT: u16 = 5684;
 
@@ -7,3 +20,165 @@ pub const PSK: &[u8] = b"";
 
 pub const SECURITY_TAG: u32 = 1;
 
+/// Minimum tank level increase (%) that is considered a refill
+pub const REFILL_THRESHOLD: u32 = 20;
+/// Number of consecutive samples the increase must persist before a refill is reported
+pub const REFILL_CONFIRM_SAMPLES: u8 = 3;
+/// Weight given to each new sample when filtering the level for the consumption statistics
+pub const CONSUMPTION_FILTER_WEIGHT: f32 = 0.1;
+
+/// Leak detection from the tank level drop rate.  Windows are an hour long so a level that
+/// flickers by a step does not read as a fast drop, and the rate must stay high for 3 hours
+pub const LEAK: LeakParams = LeakParams {
+    window_secs: 3600,
+    confirm_windows: 3,
+    // Drop rate (%/hour) that always raises a leak alarm, regardless of learned consumption
+    min_rate: 2.0,
+    alarm_factor: 5.0,
+    clear_factor: 2.0,
+    baseline_weight: 0.1,
+    // Levels are whole percent
+    step: 1,
+};
+
+/// Hall sensor fault thresholds (raw ADC).  The input is pulled down, a sensor that moves it by
+/// less than 200 between off and on did not power up
+pub const SENSOR_FAULTS: FaultParams = FaultParams {
+    open_max: 100,
+    short_min: 3900,
+    min_response: 200,
+    stuck_samples: 20,
+};
+
+/// State of charge (%) at or below which the battery is reported as low
+pub const LOW_BATTERY_SOC: u8 = 15;
+
+/// Time between sensor measurements (seconds) and number of samples per uplink in normal operation
+pub const SAMPLE_INTERVAL_SECS: u64 = 3;
+pub const BATCH_SIZE: usize = 6;
+/// Time between sensor measurements (seconds) and number of samples per uplink when saving power
+pub const SAVER_SAMPLE_INTERVAL_SECS: u64 = 12;
+pub const SAVER_BATCH_SIZE: usize = 12;
+/// Largest number of samples held before transmitting
+pub const MAX_BATCH_SIZE: usize = 12;
+
+/// Battery voltage (mV, load compensated) below which the power saving mode is entered
+#[cfg(feature = "board-stratus")]
+pub const POWER_SAVER_MV: u32 = 3400;
+#[cfg(feature = "board-icarus")]
+pub const POWER_SAVER_MV: u32 = 3600;
+/// Battery voltage (mV, load compensated) below which the device reports and shuts down
+#[cfg(feature = "board-stratus")]
+pub const POWER_CRITICAL_MV: u32 = 3200;
+#[cfg(feature = "board-icarus")]
+pub const POWER_CRITICAL_MV: u32 = 3450;
+/// Battery voltage must recover this much (mV) above a threshold to leave the power saving mode
+pub const POWER_HYSTERESIS_MV: u32 = 50;
+
+/// Safe battery charging temperature range (°C), charging is disabled outside of it.  Compared
+/// against the modem die temperature (%XTEMP), there is no battery temperature sensor, so keep a
+/// margin for the battery being colder or warmer than the modem
+pub const CHARGE_MIN_TEMP: i32 = 0;
+pub const CHARGE_MAX_TEMP: i32 = 45;
+/// Temperature must return this far (°C) inside the safe range before charging is enabled again
+pub const CHARGE_TEMP_HYSTERESIS: i32 = 3;
+
+/// SIM used at start up, the other SIM is used if it does not register to the network
+#[cfg(feature = "board-icarus")]
+pub const PREFERRED_SIM: Sim = Sim::External;
+/// Time allowed for a SIM to register to the network (seconds)
+#[cfg(feature = "board-icarus")]
+pub const SIM_REGISTRATION_TIMEOUT_SECS: u64 = 300;
+/// Consecutive failed uplinks before switching to the other SIM
+#[cfg(feature = "board-icarus")]
+pub const SIM_FALLBACK_TIMEOUTS: u8 = 3;
+
+/// Time allowed for an uplink (seconds), and until the first attach after power up.  A cold
+/// attach scans all bands and can take many minutes
+pub const UPLINK_TIMEOUT_SECS: u64 = 30;
+pub const FIRST_UPLINK_TIMEOUT_SECS: u64 = 1800;
+/// Time allowed to register to the network before an uplink gives up (seconds), below the uplink
+/// timeout so no coverage is reported as `Error::NoService`
+pub const REGISTRATION_TIMEOUT_SECS: u64 = UPLINK_TIMEOUT_SECS - 10;
+pub const FIRST_REGISTRATION_TIMEOUT_SECS: u64 = FIRST_UPLINK_TIMEOUT_SECS - 20;
+
+/// Modem power saving profile: PSM, or eDRX to stay reachable for downlink
+pub const MODEM_POWER: ModemPower = ModemPower::Psm;
+
+/// Requested LTE PSM periodic TAU (T3412) and active time (T3324) in seconds, the network may
+/// grant other values
+pub const PSM_PERIODIC_TAU_SECS: u32 = 3600;
+pub const PSM_ACTIVE_TIME_SECS: u32 = 10;
+
+/// Requested eDRX cycle and paging time window (ms), rounded up to the values LTE-M and NB-IoT
+/// each support
+pub const EDRX_CYCLE_MS: u32 = 81920;
+pub const EDRX_PTW_MS: u32 = 2560;
+
+/// Release Assistance Indication with the last uplink of each connection.  `send` does not wait
+/// for the server's ACK, so the connection is released right after the uplink
+pub const RELEASE_ASSISTANCE: ReleaseAssistance = ReleaseAssistance::NoResponse;
+
+/// Time between diagnostics uplinks (seconds)
+pub const DIAGNOSTICS_INTERVAL_SECS: u64 = 24 * 3600;
+
+/// Time between serving cell information uplinks (seconds)
+pub const NETWORK_INFO_INTERVAL_SECS: u64 = 24 * 3600;
+
+/// Time between cell location uplinks (seconds)
+pub const CELL_LOCATION_INTERVAL_SECS: u64 = 24 * 3600;
+/// Time before a failed cell measurement is tried again (seconds)
+pub const CELL_LOCATION_RETRY_SECS: u64 = 3600;
+
+/// Period of the external wake up timer (seconds), used for timestamps in deep sleep
+#[cfg(feature = "deep-sleep")]
+pub const DEEP_SLEEP_INTERVAL_SECS: u32 = 3600;
+
+/// Time after boot the status LED is shown for (seconds), long enough to install the unit
+pub const STATUS_LED_TIMEOUT_SECS: u64 = 1800;
+
+/// Tanks connected to the device, `channel` selects the board's sensor input and power pin pair.
+/// Each tank must use a different channel.  A pressure transducer is configured with e.g.
+/// `sensor: SensorKind::Pressure(PressureProfile { max_pressure: 2000, ..PressureProfile::CURRENT_LOOP })`
+pub const TANKS: [TankConfig; 1] = [TankConfig {
+    id: 1,
+    channel: 0,
+    sensor: SensorKind::HallEffect(SensorProfile::HALL_GAUGE),
+}];
+pub const TANK_COUNT: usize = TANKS.len();
+
+/// Acceleration (mg, high pass filtered) that wakes the tamper detection
+pub const TAMPER_MOTION_MG: u32 = 250;
+/// Cosine of the tilt angle from the installed orientation reported as a tilt (20°)
+pub const TAMPER_TILT_COS: f32 = 0.94;
+/// Cosine of the tilt angle from the installed orientation reported as a removal (60°)
+pub const TAMPER_REMOVED_COS: f32 = 0.5;
+/// Minimum time between tamper alerts (seconds)
+pub const TAMPER_HOLDOFF_SECS: u64 = 600;
+
+/// Modbus RTU serial settings, 8 data bits with even parity
+#[cfg(feature = "modbus")]
+pub const MODBUS_BAUDRATE: Baudrate = Baudrate::BAUD9600;
+/// Time allowed for a slave to respond (ms)
+#[cfg(feature = "modbus")]
+pub const MODBUS_TIMEOUT_MS: u64 = 500;
+/// Registers polled before each uplink
+#[cfg(feature = "modbus")]
+pub const MODBUS_POINTS: [ModbusPoint; 1] = [ModbusPoint {
+    name: "level",
+    slave: 1,
+    kind: RegisterKind::Holding,
+    address: 0,
+    format: RegisterFormat::U16,
+}];
+#[cfg(feature = "modbus")]
+pub const MODBUS_POINT_COUNT: usize = MODBUS_POINTS.len();
+
+/// Estimated current draw (uA) in each state, used for the energy accounting.  Measure them on
+/// the board with a power profiler, the battery life predictions are only as good as these
+pub const CURRENT_SLEEP_UA: u32 = 10;
+pub const CURRENT_SYSTEM_OFF_UA: u32 = 2;
+pub const CURRENT_SENSOR_UA: u32 = 7000;
+pub const CURRENT_ADC_UA: u32 = 1000;
+pub const CURRENT_CONNECTING_UA: u32 = 30000;
+pub const CURRENT_TRANSMITTING_UA: u32 = 60000;
@@ -0,0 +1,117 @@
+//! Deep sleep between measurements, enabled with the `deep-sleep` cargo feature.  The device
+//! enters System OFF between measurements, where only a pin can wake it, so the sample interval
+//! comes from an external nano-power timer (e.g. TPL5111) on the wake pin.  The modem is powered
+//! down in System OFF and cannot wake the device, see `power::system_off`.  The batch and the tank monitors are kept in
+//! retained RAM and picked up again after the wake up reset.
+use crate::battery::BatteryMonitor;
+use crate::config::{DEEP_SLEEP_INTERVAL_SECS, TANK_COUNT};
+use crate::energy::EnergyMeter;
+use crate::periodic::PeriodicUplinks;
+use crate::power::{system_off, PowerPolicy};
+#[cfg(feature = "board-icarus")]
+use crate::sim::SimManager;
+use crate::tank::TankMonitor;
+use crate::Payload;
+use core::mem::{size_of, MaybeUninit};
+use defmt::info;
+use embassy_nrf::gpio::{AnyPin, Pin};
+use embassy_nrf::pac;
+use heapless::Vec;
+
+#[cfg(feature = "tamper")]
+compile_error!("The `tamper` feature cannot be used with `deep-sleep`");
+
+/// Marks valid retained state, includes the state size so a firmware update with a different
+/// layout starts from scratch
+const MAGIC: u32 = 0x5EE9_0000 ^ size_of::<RetainedState>() as u32;
+
+/// Application state kept through System OFF
+pub struct RetainedState {
+    pub payload: Payload,
+    pub tanks: Vec<TankMonitor, TANK_COUNT>,
+    pub battery: BatteryMonitor,
+    pub power: PowerPolicy,
+    pub periodic: PeriodicUplinks,
+    /// Icarus: the SIM selected after power up, the selection is not repeated on wake up
+    #[cfg(feature = "board-icarus")]
+    pub sim: SimManager,
+    pub energy: EnergyMeter,
+    /// Registered since power up, see `registration::attached`
+    pub attached: bool,
+    /// Seconds since the first boot, the uptime counter restarts on every wake up
+    pub clock: u32,
+}
+
+struct Retained {
+    magic: u32,
+    state: MaybeUninit<RetainedState>,
+}
+
+/// Not zeroed by the startup code, RAM retention keeps it through System OFF
+#[link_section = ".uninit.retained"]
+static mut RETAINED: MaybeUninit<Retained> = MaybeUninit::uninit();
+
+/// The state saved by `sleep` if the device woke up from System OFF, `None` after any other reset.
+/// `clock` and the energy meter are advanced by the sleep interval
+pub fn restore() -> Option<RetainedState> {
+    let power = unsafe { &*pac::POWER::PTR };
+    let woke = power.resetreas.read().off().is_detected();
+    // Reset reasons are latched until cleared
+    power.resetreas.write(|w| unsafe { w.bits(0xFFFF_FFFF) });
+
+    // Safety: only touched here and in `sleep`, before and after the application runs
+    let retained = unsafe { RETAINED.assume_init_mut() };
+    if !woke || retained.magic != MAGIC {
+        return None;
+    }
+    retained.magic = 0;
+
+    let mut state = unsafe { retained.state.assume_init_read() };
+    state.clock += DEEP_SLEEP_INTERVAL_SECS;
+    state.energy.system_off(DEEP_SLEEP_INTERVAL_SECS);
+    info!(
+        "Woke up from System OFF, {} samples in the batch",
+        state.payload.samples()
+    );
+    Some(state)
+}
+
+/// Save the state to retained RAM and enter System OFF until `wake` goes high
+pub async fn sleep(state: RetainedState, wake: AnyPin) -> ! {
+    unsafe {
+        RETAINED.write(Retained {
+            magic: MAGIC,
+            state: MaybeUninit::new(state),
+        });
+    }
+
+    // Keep all RAM sections powered in System OFF
+    let vmc = unsafe { &*pac::VMC::PTR };
+    for ram in vmc.ram.iter() {
+        ram.power.modify(|_, w| {
+            w.s0retention()
+                .on()
+                .s1retention()
+                .on()
+                .s2retention()
+                .on()
+                .s3retention()
+                .on()
+        });
+    }
+
+    // Wake up on the rising edge of the external timer
+    let port = unsafe { &*pac::P0::PTR };
+    port.pin_cnf[wake.pin() as usize].write(|w| {
+        w.dir()
+            .input()
+            .input()
+            .connect()
+            .pull()
+            .pulldown()
+            .sense()
+            .high()
+    });
+
+    system_off().await
+}
@@ -0,0 +1,32 @@
+//! Diagnostics uplink, sent after boot and then daily with the modem configuration the network
+//! agreed to and the estimated energy used.  Sent to `.s/diagnostics`, separate from the tank data.
+use crate::edrx::EdrxValues;
+use crate::energy::{self, EnergyReport};
+use crate::power::ModemPower;
+use crate::psm::PsmTimers;
+use serde::Serialize;
+
+/// Device diagnostics, the negotiated values are read while connected to send it
+#[derive(Debug, Serialize)]
+pub struct Diagnostics {
+    pub modem_power: ModemPower,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub psm: Option<PsmTimers>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub edrx: Option<EdrxValues>,
+    pub energy: EnergyReport,
+    pub timestamp: u32,
+}
+
+/// Diagnostics constructor
+impl Diagnostics {
+    pub fn new(modem_power: ModemPower, timestamp: u32) -> Self {
+        Diagnostics {
+            modem_power,
+            psm: None,
+            edrx: None,
+            energy: energy::report(),
+            timestamp,
+        }
+    }
+}
@@ -0,0 +1,149 @@
+//! LTE extended discontinuous reception (eDRX), the alternative to PSM when the device must stay
+//! reachable for downlink.  The modem listens for paging for a paging time window (PTW) once every
+//! eDRX cycle.  The network decides the values in use, they are reported with +CEDRXP, picked up
+//! by the `registration` notification handler, and read with AT+CEDRXRDP until the first one.
+use crate::at::{get_edrx, set_psm, Edrx};
+use crate::config::{EDRX_CYCLE_MS, EDRX_PTW_MS};
+use crate::Error;
+use core::cell::Cell;
+use core::fmt::write;
+use defmt::{error, info, Format};
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::blocking_mutex::Mutex;
+use heapless::String;
+use serde::Serialize;
+
+/// eDRX values of an access technology
+struct Tables {
+    /// Access technology in the eDRX commands
+    act: i32,
+    /// eDRX cycle lengths (ms) by value, 0 for values not used by the access technology
+    cycles: [u32; 16],
+    /// Paging time window step (ms), PTW = (value + 1) * step
+    ptw_step: u32,
+}
+
+const LTE_M: Tables = Tables {
+    act: 4,
+    cycles: [
+        5120, 10240, 20480, 40960, 61440, 81920, 102400, 122880, 143360, 163840, 327680, 655360,
+        1310720, 2621440, 5242880, 10485760,
+    ],
+    ptw_step: 1280,
+};
+
+const NB_IOT: Tables = Tables {
+    act: 5,
+    cycles: [
+        0, 0, 20480, 40960, 0, 81920, 0, 0, 0, 163840, 327680, 655360, 1310720, 2621440, 5242880,
+        10485760,
+    ],
+    ptw_step: 2560,
+};
+
+impl Tables {
+    /// Tables of the access technology `act`, `None` when eDRX is not in use
+    fn of(act: i32) -> Option<&'static Tables> {
+        [&LTE_M, &NB_IOT]
+            .into_iter()
+            .find(|tables| tables.act == act)
+    }
+}
+
+/// Values of the last +CEDRXP notification
+static NOTIFIED: Mutex<CriticalSectionRawMutex, Cell<Option<EdrxValues>>> =
+    Mutex::new(Cell::new(None));
+
+/// Network provided eDRX values, `None` when eDRX is not in use
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Format, Serialize)]
+pub struct EdrxValues {
+    pub cycle_ms: Option<u32>,
+    pub ptw_ms: Option<u32>,
+}
+
+impl EdrxValues {
+    /// The values in use, from the last +CEDRXP notification or read before the first one
+    pub async fn negotiated() -> Result<Self, Error> {
+        if let Some(values) = NOTIFIED.lock(|notified| notified.get()) {
+            return Ok(values);
+        }
+        Ok(Self::decode(get_edrx().await?))
+    }
+
+    /// Parse a +CEDRXP notification
+    fn from_notification(notification: &str) -> Result<Self, Error> {
+        Ok(Self::decode(Edrx::parse_notification(notification)?))
+    }
+
+    /// The values are coded differently for LTE-M and NB-IoT
+    fn decode(values: Edrx) -> Self {
+        match Tables::of(values.act) {
+            Some(tables) => EdrxValues {
+                cycle_ms: values
+                    .edrx
+                    .and_then(|v| tables.cycles.get(v as usize).copied())
+                    .filter(|cycle| *cycle != 0),
+                ptw_ms: values.ptw.map(|v| (v as u32 + 1) * tables.ptw_step),
+            },
+            None => EdrxValues {
+                cycle_ms: None,
+                ptw_ms: None,
+            },
+        }
+    }
+}
+
+/// Keep the values of a +CEDRXP notification, sent when the network changes them
+pub fn notified(notification: &str) {
+    match EdrxValues::from_notification(notification) {
+        Ok(values) => {
+            info!("eDRX values: {}", values);
+            NOTIFIED.lock(|notified| notified.set(Some(values)));
+        }
+        Err(e) => error!("Bad +CEDRXP notification: {:?}", defmt::Debug2Format(&e)),
+    }
+}
+
+/// Request the configured eDRX cycle and paging time window for LTE-M and NB-IoT, with +CEDRXP
+/// notifications.  PSM is turned off, the device would not be reachable in PSM
+pub async fn request_edrx() -> Result<(), Error> {
+    set_psm(None).await?;
+    for tables in [&LTE_M, &NB_IOT] {
+        request(tables).await?;
+    }
+    Ok(())
+}
+
+/// Request the shortest cycle and window at least as long as configured
+async fn request(tables: &Tables) -> Result<(), Error> {
+    let cycle = tables
+        .cycles
+        .iter()
+        .position(|c| *c >= EDRX_CYCLE_MS)
+        .unwrap_or(tables.cycles.len() - 1);
+    let ptw = ((EDRX_PTW_MS + tables.ptw_step - 1) / tables.ptw_step).clamp(1, 16) - 1;
+
+    let mut cmd: String<32> = String::new();
+    write(
+        &mut cmd,
+        format_args!(r#"AT+CEDRXS=2,{},"{:04b}""#, tables.act, cycle),
+    )
+    .unwrap();
+    nrf_modem::send_at::<32>(cmd.as_str()).await?;
+
+    cmd.clear();
+    write(
+        &mut cmd,
+        format_args!(r#"AT%XPTW={},"{:04b}""#, tables.act, ptw),
+    )
+    .unwrap();
+    nrf_modem::send_at::<32>(cmd.as_str()).await?;
+
+    info!(
+        "eDRX requested for AcT {}: cycle {} ms, PTW {} ms",
+        tables.act,
+        tables.cycles[cycle],
+        (ptw + 1) * tables.ptw_step
+    );
+    Ok(())
+}
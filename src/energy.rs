@@ -0,0 +1,167 @@
+//! Energy accounting.  The time spent in each state is recorded and multiplied by the estimated
+//! current draw of the state from `config` to get the charge used.  The application and the
+//! uplink path mark state changes with `enter`, the totals are reported with the diagnostics.
+use crate::config::{
+    CURRENT_ADC_UA, CURRENT_CONNECTING_UA, CURRENT_SENSOR_UA, CURRENT_SLEEP_UA,
+    CURRENT_SYSTEM_OFF_UA, CURRENT_TRANSMITTING_UA,
+};
+use core::cell::RefCell;
+use defmt::Format;
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::blocking_mutex::Mutex;
+use embassy_time::Instant;
+use serde::Serialize;
+
+/// Number of states in `EnergyState`
+const STATES: usize = 6;
+
+/// Device state for the energy accounting
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Format)]
+pub enum EnergyState {
+    /// CPU idle, modem in PSM or idle
+    Sleep,
+    /// System OFF between measurements (deep sleep)
+    SystemOff,
+    /// Tank sensors powered and sampled
+    Sensor,
+    /// Battery measurement
+    Adc,
+    /// Modem registering and opening the DTLS connection
+    Connecting,
+    /// Sending the uplink
+    Transmitting,
+}
+
+impl EnergyState {
+    /// Estimated current draw (uA)
+    fn current(&self) -> u32 {
+        match self {
+            EnergyState::Sleep => CURRENT_SLEEP_UA,
+            EnergyState::SystemOff => CURRENT_SYSTEM_OFF_UA,
+            EnergyState::Sensor => CURRENT_SENSOR_UA,
+            EnergyState::Adc => CURRENT_ADC_UA,
+            EnergyState::Connecting => CURRENT_CONNECTING_UA,
+            EnergyState::Transmitting => CURRENT_TRANSMITTING_UA,
+        }
+    }
+}
+
+/// Time spent in each state and the estimated charge used since the first boot
+#[derive(Debug, Clone, Copy)]
+pub struct EnergyMeter {
+    state: EnergyState,
+    since: Instant,
+    /// Time in each state (ms), indexed by `EnergyState`
+    time: [u64; STATES],
+    /// Charge used (uA ms), kept unscaled so short states are not rounded away
+    charge: u64,
+}
+
+impl EnergyMeter {
+    const fn new() -> Self {
+        EnergyMeter {
+            state: EnergyState::Sleep,
+            since: Instant::from_ticks(0),
+            time: [0; STATES],
+            charge: 0,
+        }
+    }
+
+    /// Account for `ms` spent in `state`
+    fn add(&mut self, state: EnergyState, ms: u64) {
+        self.time[state as usize] += ms;
+        self.charge += state.current() as u64 * ms;
+    }
+
+    /// Close the current state and switch to `state`
+    fn enter(&mut self, state: EnergyState) {
+        let now = Instant::now();
+        self.add(self.state, (now - self.since).as_millis());
+        self.state = state;
+        self.since = now;
+    }
+
+    /// Account for `secs` spent in System OFF, the uptime counter does not run through it
+    #[cfg(feature = "deep-sleep")]
+    pub(crate) fn system_off(&mut self, secs: u32) {
+        self.add(EnergyState::SystemOff, secs as u64 * 1000);
+    }
+
+    fn report(&self) -> EnergyReport {
+        let secs = |state: EnergyState| (self.time[state as usize] / 1000) as u32;
+        EnergyReport {
+            sleep: secs(EnergyState::Sleep),
+            system_off: secs(EnergyState::SystemOff),
+            sensor: secs(EnergyState::Sensor),
+            adc: secs(EnergyState::Adc),
+            connecting: secs(EnergyState::Connecting),
+            transmitting: secs(EnergyState::Transmitting),
+            used_mah: (self.charge / 1000) as f32 / 3_600_000.0,
+        }
+    }
+}
+
+/// Energy totals for the uplink, time in each state in seconds
+#[derive(Debug, Clone, Copy, Format, Serialize)]
+pub struct EnergyReport {
+    pub sleep: u32,
+    pub system_off: u32,
+    pub sensor: u32,
+    pub adc: u32,
+    pub connecting: u32,
+    pub transmitting: u32,
+    /// Estimated charge used (mAh)
+    pub used_mah: f32,
+}
+
+static METER: Mutex<CriticalSectionRawMutex, RefCell<EnergyMeter>> =
+    Mutex::new(RefCell::new(EnergyMeter::new()));
+
+/// Record a state change
+pub fn enter(state: EnergyState) {
+    METER.lock(|meter| meter.borrow_mut().enter(state));
+}
+
+/// Enter `state` until the returned guard is dropped, then go back to `Sleep`.  Used for the
+/// modem states so an error or a cancelled timeout cannot leave the meter counting them.
+pub fn active(state: EnergyState) -> Active {
+    enter(state);
+    Active
+}
+
+/// Returned by `active`, enters `Sleep` when dropped
+#[must_use]
+pub struct Active;
+
+impl Drop for Active {
+    fn drop(&mut self) {
+        enter(EnergyState::Sleep);
+    }
+}
+
+/// Totals up to now
+pub fn report() -> EnergyReport {
+    METER.lock(|meter| {
+        let mut meter = meter.borrow_mut();
+        let state = meter.state;
+        meter.enter(state);
+        meter.report()
+    })
+}
+
+/// Copy of the meter, to keep it through System OFF
+pub fn snapshot() -> EnergyMeter {
+    METER.lock(|meter| {
+        let mut meter = meter.borrow_mut();
+        let state = meter.state;
+        meter.enter(state);
+        *meter
+    })
+}
+
+/// Continue from a meter saved with `snapshot`
+pub fn restore(mut saved: EnergyMeter) {
+    saved.state = EnergyState::Sleep;
+    saved.since = Instant::now();
+    METER.lock(|meter| *meter.borrow_mut() = saved);
+}
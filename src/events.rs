@@ -0,0 +1,126 @@
+use crate::sensor::SensorFault;
+#[cfg(feature = "tamper")]
+use crate::tamper::TamperKind;
+use serde::Serialize;
+
+/// Refill event, sent as soon as a sustained level increase is detected
+#[derive(Debug, Serialize)]
+pub struct RefillEvent {
+    event: &'static str,
+    pub tank: u8,
+    pub before: u32,
+    pub after: u32,
+    pub timestamp: u32,
+    pub consumed: u32,
+}
+
+/// RefillEvent constructor
+impl RefillEvent {
+    pub fn new(tank: u8, before: u32, after: u32, timestamp: u32) -> Self {
+        RefillEvent {
+            event: "refill",
+            tank,
+            before,
+            after,
+            timestamp,
+            consumed: 0,
+        }
+    }
+}
+
+/// Leak alarm, sent immediately when the level drops far faster than normal consumption
+#[derive(Debug, Serialize)]
+pub struct LeakAlarm {
+    event: &'static str,
+    pub tank: u8,
+    pub level: u32,
+    pub rate: f32,
+    pub baseline: f32,
+    pub timestamp: u32,
+}
+
+/// LeakAlarm constructor
+impl LeakAlarm {
+    pub fn new(tank: u8, level: u32, rate: f32, baseline: f32, timestamp: u32) -> Self {
+        LeakAlarm {
+            event: "leak",
+            tank,
+            level,
+            rate,
+            baseline,
+            timestamp,
+        }
+    }
+}
+
+/// Sensor fault event, sent whenever the sensor fault status changes
+#[derive(Debug, Serialize)]
+pub struct FaultEvent {
+    event: &'static str,
+    pub tank: u8,
+    pub fault: SensorFault,
+    pub raw: i16,
+    pub timestamp: u32,
+}
+
+/// FaultEvent constructor
+impl FaultEvent {
+    pub fn new(tank: u8, fault: SensorFault, raw: i16, timestamp: u32) -> Self {
+        FaultEvent {
+            event: "sensor_fault",
+            tank,
+            fault,
+            raw,
+            timestamp,
+        }
+    }
+}
+
+/// Battery critical event, the last message sent before the device shuts down
+#[derive(Debug, Serialize)]
+pub struct BatteryCritical {
+    event: &'static str,
+    pub battery: u32,
+    pub soc: u8,
+    pub timestamp: u32,
+}
+
+/// BatteryCritical constructor
+impl BatteryCritical {
+    pub fn new(battery: u32, soc: u8, timestamp: u32) -> Self {
+        BatteryCritical {
+            event: "battery_critical",
+            battery,
+            soc,
+            timestamp,
+        }
+    }
+}
+
+/// Tamper alert, sent when the device is moved, the tank tilted or the device removed
+#[cfg(feature = "tamper")]
+#[derive(Debug, Serialize)]
+pub struct TamperAlert {
+    event: &'static str,
+    pub kind: TamperKind,
+    /// Acceleration after the motion (mg)
+    pub x: i16,
+    pub y: i16,
+    pub z: i16,
+    pub timestamp: u32,
+}
+
+/// TamperAlert constructor
+#[cfg(feature = "tamper")]
+impl TamperAlert {
+    pub fn new(kind: TamperKind, accel: [i16; 3], timestamp: u32) -> Self {
+        TamperAlert {
+            event: "tamper",
+            kind,
+            x: accel[0],
+            y: accel[1],
+            z: accel[2],
+            timestamp,
+        }
+    }
+}
@@ -0,0 +1,120 @@
+use crate::config::{CONSUMPTION_FILTER_WEIGHT, LEAK, REFILL_CONFIRM_SAMPLES, REFILL_THRESHOLD};
+use crate::events::{LeakAlarm, RefillEvent};
+use propane_monitor_core::leak;
+
+/// Tank consumption since the last refill (or power up).  The consumption is the start level
+/// minus the lowest filtered level, so a level that flickers by a step is not counted over and
+/// over, and the increase before a refill is confirmed does not take consumption back.
+#[derive(Debug)]
+pub struct ConsumptionStats {
+    start_level: u32,
+    filtered: f32,
+    lowest: f32,
+}
+
+impl ConsumptionStats {
+    pub fn new(level: u32) -> Self {
+        ConsumptionStats {
+            start_level: level,
+            filtered: level as f32,
+            lowest: level as f32,
+        }
+    }
+
+    /// Record a new sample
+    pub fn update(&mut self, level: u32) {
+        self.filtered += (level as f32 - self.filtered) * CONSUMPTION_FILTER_WEIGHT;
+        self.lowest = self.lowest.min(self.filtered);
+    }
+
+    /// Start the statistics over from the given level, used after a refill
+    pub fn reset(&mut self, level: u32) {
+        *self = ConsumptionStats::new(level);
+    }
+
+    /// Level (%) consumed since the statistics were started
+    pub fn consumed(&self) -> u32 {
+        self.start_level
+            .saturating_sub(libm::roundf(self.lowest) as u32)
+    }
+}
+
+/// Detects a sustained tank level increase larger than `REFILL_THRESHOLD`
+pub struct RefillDetector {
+    tank: u8,
+    threshold: u32,
+    confirm_samples: u8,
+    reference: Option<u32>,
+    count: u8,
+}
+
+impl RefillDetector {
+    pub fn new(tank: u8) -> Self {
+        RefillDetector {
+            tank,
+            threshold: REFILL_THRESHOLD,
+            confirm_samples: REFILL_CONFIRM_SAMPLES,
+            reference: None,
+            count: 0,
+        }
+    }
+
+    /// Feed a new sample, returns a refill event once the increase has persisted for
+    /// `REFILL_CONFIRM_SAMPLES` consecutive samples
+    pub fn update(&mut self, level: u32, timestamp: u32) -> Option<RefillEvent> {
+        let reference = *self.reference.get_or_insert(level);
+
+        if level < reference + self.threshold {
+            // Not a refill, follow the level so slow drift doesn't accumulate
+            self.reference = Some(level);
+            self.count = 0;
+            return None;
+        }
+
+        self.count += 1;
+        if self.count < self.confirm_samples {
+            return None;
+        }
+
+        self.reference = Some(level);
+        self.count = 0;
+        Some(RefillEvent::new(self.tank, reference, level, timestamp))
+    }
+}
+
+/// Leak detection for a single tank, see `propane_monitor_core::leak`
+pub struct LeakDetector {
+    tank: u8,
+    detector: leak::LeakDetector,
+}
+
+impl LeakDetector {
+    pub fn new(tank: u8) -> Self {
+        LeakDetector {
+            tank,
+            detector: leak::LeakDetector::new(LEAK),
+        }
+    }
+
+    /// Learned baseline consumption rate in percent per hour
+    pub fn baseline(&self) -> Option<f32> {
+        self.detector.baseline()
+    }
+
+    /// Start a new window, used after a refill so the jump is not seen as a rate change
+    pub fn reset(&mut self) {
+        self.detector.reset();
+    }
+
+    /// Feed a new sample, returns a leak alarm when the drop rate stayed abnormal long enough
+    pub fn update(&mut self, level: u32, timestamp: u32) -> Option<LeakAlarm> {
+        let leak = self.detector.update(level, timestamp)?;
+        Some(LeakAlarm::new(
+            self.tank,
+            level,
+            leak.rate,
+            leak.baseline,
+            timestamp,
+        ))
+    }
+}
@@ -5,24 +5,72 @@
 extern crate alloc;
 extern crate tinyrlibc;
 
-mod at;
+pub mod at;
+pub mod battery;
+pub mod board;
+pub mod charger;
 mod config;
+#[cfg(feature = "deep-sleep")]
+pub mod deep_sleep;
+pub mod diagnostics;
+pub mod edrx;
+pub mod energy;
+pub mod events;
 mod gnss;
+pub mod level;
+pub mod location;
+#[cfg(feature = "modbus")]
+pub mod modbus;
+pub mod network;
+pub mod periodic;
+pub mod power;
 pub mod psk;
+pub mod psm;
+pub mod radio;
+pub mod rai;
+pub mod registration;
+pub mod sensor;
+#[cfg(feature = "board-icarus")]
+pub mod sim;
+pub mod status;
+#[cfg(feature = "tamper")]
+pub mod tamper;
+pub mod tank;
+#[cfg(feature = "ultrasonic")]
+pub mod ultrasonic;
 
-use crate::at::*;
-use crate::config::{SECURITY_TAG, SERVER_PORT, SERVER_URL};
+use crate::at::ServingCell;
+use crate::charger::ChargeStatus;
+use crate::config::{
+    CELL_LOCATION_RETRY_SECS, MAX_BATCH_SIZE, SECURITY_TAG, SERVER_PORT, SERVER_URL, TANKS,
+    TANK_COUNT,
+};
+use crate::diagnostics::Diagnostics;
+use crate::edrx::EdrxValues;
+use crate::energy::EnergyState;
+use crate::location::{measure_cells, CellLocation};
+use crate::network::NetworkInfo;
+use crate::periodic::PeriodicUplinks;
+use crate::power::{ModemPower, PowerProfile};
+use crate::psm::PsmTimers;
+use crate::radio::{RadioQuality, RadioStats};
+use crate::sensor::SensorFault;
+#[cfg(feature = "board-icarus")]
+use crate::sim::SimStatus;
+use crate::tank::SensorProfile;
+#[cfg(feature = "modbus")]
+use crate::{config::MODBUS_POINT_COUNT, modbus::ModbusValue};
 use alloc_cortex_m::CortexMHeap;
-use at_commands::parser::ParseError;
 use coap_lite::error::MessageError;
 use coap_lite::{CoapRequest, ContentFormat, RequestType};
 use core::mem::MaybeUninit;
 use core::sync::atomic::{AtomicBool, Ordering};
-use defmt::info;
+use defmt::{error, info};
 use embassy_nrf as _;
 use embassy_time::TimeoutError;
 use heapless::Vec;
 use nrf_modem::{DtlsSocket, PeerVerification};
+use propane_monitor_core::modbus::FrameError;
 use serde::Serialize;
 use {defmt_rtt as _, panic_probe as _};
 
@@ -39,7 +87,29 @@ pub enum Error {
     Json(serde_json::error::Error),
     NrfModem(nrf_modem::Error),
     Timeout(TimeoutError),
-    ParseError(ParseError),
+    At(propane_monitor_core::at::Error),
+    Twim(embassy_nrf::twim::Error),
+    Uarte(embassy_nrf::uarte::Error),
+    /// Unexpected accelerometer WHO_AM_I value
+    Accelerometer(u8),
+    /// Serial frame failed checksum validation
+    Checksum,
+    /// Serial frame does not match the request
+    InvalidFrame,
+    /// Neighbor cell measurement failed or was interrupted
+    CellMeasurement,
+    /// None of the due periodic uplinks could be read from the modem
+    NothingToSend,
+    /// Modbus exception response with the exception code
+    Modbus(u8),
+    /// The modem did not register to the network
+    NotRegistered,
+    /// The network rejected the registration
+    RegistrationDenied,
+    /// No network found, out of coverage or no usable SIM
+    NoService,
+    /// Setting a socket option failed with the modem library error
+    SocketOption(i32),
 }
 
 impl From<MessageError> for Error {
@@ -66,86 +136,324 @@ impl From<TimeoutError> for Error {
     }
 }
 
-impl From<ParseError> for Error {
-    fn from(e: ParseError) -> Self {
-        Self::ParseError(e)
+impl From<propane_monitor_core::at::Error> for Error {
+    fn from(e: propane_monitor_core::at::Error) -> Self {
+        Self::At(e)
     }
 }
 
-/// Payload to send over CoAP (Heapless Vec of Tanklevel Structs)
+impl From<FrameError> for Error {
+    fn from(e: FrameError) -> Self {
+        match e {
+            FrameError::Checksum => Self::Checksum,
+            FrameError::InvalidFrame => Self::InvalidFrame,
+            FrameError::Exception(code) => Self::Modbus(code),
+        }
+    }
+}
+
+impl From<embassy_nrf::twim::Error> for Error {
+    fn from(e: embassy_nrf::twim::Error) -> Self {
+        Self::Twim(e)
+    }
+}
+
+impl From<embassy_nrf::uarte::Error> for Error {
+    fn from(e: embassy_nrf::uarte::Error) -> Self {
+        Self::Uarte(e)
+    }
+}
+
+/// Payload to send over CoAP (Heapless Vec of Tanklevel Structs for each tank)
 #[derive(Debug, Serialize)]
-pub struct Payload<'a> {
-    pub data: Vec<TankLevel, 6>,
-    pub signal: i32,
+pub struct Payload {
+    pub tanks: Vec<TankData, TANK_COUNT>,
+    /// RSRP (dBm) read during the uplink, null when unknown
+    pub signal: Option<i32>,
+    pub radio: RadioStats,
     pub message: u8,
     pub timeouts: u8,
-    location: &'a str,
+    pub soc: u8,
+    pub low_battery: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub charger: Option<ChargeStatus>,
+    #[cfg(feature = "modbus")]
+    pub modbus: Vec<ModbusValue, MODBUS_POINT_COUNT>,
+    #[cfg(feature = "board-icarus")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sim: Option<SimStatus>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub psm: Option<PsmTimers>,
+    /// Serving cell of the last cell measurement, the position is resolved from `.s/cells`
+    pub location: Option<ServingCell>,
 }
 
 /// Payload constructor
-impl Payload<'_> {
+impl Payload {
     pub fn new() -> Self {
         Payload {
-            data: Vec::new(),
-            signal: 0,
+            tanks: TANKS.iter().map(|tank| TankData::new(tank.id)).collect(),
+            signal: None,
+            radio: RadioStats::new(),
             message: 0,
             timeouts: 0,
-            location: "Reliability Test 3",
+            soc: 0,
+            low_battery: false,
+            charger: None,
+            #[cfg(feature = "modbus")]
+            modbus: Vec::new(),
+            #[cfg(feature = "board-icarus")]
+            sim: None,
+            psm: None,
+            location: None,
+        }
+    }
+
+    /// Number of samples held for each tank
+    pub fn samples(&self) -> usize {
+        self.tanks.first().map_or(0, |tank| tank.data.len())
+    }
+
+    /// Clear the samples of all tanks and start a new radio metrics window
+    pub fn clear(&mut self) {
+        for tank in self.tanks.iter_mut() {
+            tank.data.clear();
         }
+        self.radio.clear();
     }
 }
 
-/// Structure to hold our individual measure data
+impl Default for Payload {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Samples of a single tank
+#[derive(Debug, Serialize)]
+pub struct TankData {
+    pub id: u8,
+    pub data: Vec<TankLevel, MAX_BATCH_SIZE>,
+}
+
+/// TankData constructor
+impl TankData {
+    pub fn new(id: u8) -> Self {
+        TankData {
+            id,
+            data: Vec::new(),
+        }
+    }
+}
+
+/// Structure to hold our individual measure data.  `value` is the tank level (%) and `pressure`
+/// the pressure (kPa), each left out when the tank's sensor does not measure it
 #[derive(Debug, Serialize)]
 pub struct TankLevel {
-    pub value: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub value: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pressure: Option<u32>,
     pub timestamp: u32,
     pub battery: u32,
+    pub fault: SensorFault,
 }
 
 /// TankLevel constructor
 impl TankLevel {
-    pub fn new(value: u32, timestamp: u32, battery: u32) -> Self {
+    pub fn new(
+        value: Option<u32>,
+        pressure: Option<u32>,
+        timestamp: u32,
+        battery: u32,
+        fault: SensorFault,
+    ) -> Self {
         TankLevel {
             value,
+            pressure,
             timestamp,
             battery,
+            fault,
         }
     }
 }
 
 /// Create CoAP request, serialize payload, and transimt data
 /// request path can start with .s/ for LightDB Stream or .d/ LightDB State for Golioth IoT
-pub async fn transmit_payload(payload: &mut Payload<'_>) -> Result<(), Error> {
-    // Create our DTLS socket
+pub async fn transmit_payload(payload: &mut Payload) -> Result<(), Error> {
+    let socket = connect().await?;
+
+    let quality = read_radio(&mut payload.radio).await;
+    payload.signal = quality.and_then(|quality| quality.rsrp);
+
+    // Network granted PSM timers, only known while registered
+    payload.psm = PsmTimers::granted().await.ok();
+
+    send(socket, ".s/tank_level", payload).await
+}
+
+/// Transmit an event message immediately, outside of the normal payload batching.  The radio
+/// quality is read into `radio` while connected
+pub async fn transmit_event<T: Serialize>(event: &T, radio: &mut RadioStats) -> Result<(), Error> {
+    let socket = connect().await?;
+    read_radio(radio).await;
+    send(socket, ".s/events", event).await
+}
+
+/// Add a radio quality reading to the batch window.  Only read while connected, the modem does not
+/// measure the serving cell while LTE is off
+async fn read_radio(radio: &mut RadioStats) -> Option<RadioQuality> {
+    match RadioQuality::read().await {
+        Ok(quality) => {
+            info!("Radio quality: {}", quality);
+            radio.add(quality);
+            Some(quality)
+        }
+        Err(e) => {
+            error!("Radio quality read failed: {:?}", defmt::Debug2Format(&e));
+            None
+        }
+    }
+}
+
+/// Send the periodic uplinks that are due in a single connection, each is marked sent once it
+/// went out, a failed cell measurement is tried again after `CELL_LOCATION_RETRY_SECS`.  The
+/// network info and cell location are only sent when `profile` runs them.  The serving cell of a
+/// cell measurement is kept in the payload location
+pub async fn transmit_periodic(
+    uplinks: &mut PeriodicUplinks,
+    payload: &mut Payload,
+    modem_power: ModemPower,
+    profile: &PowerProfile,
+    now: u32,
+) -> Result<(), Error> {
+    let diagnostics = uplinks.diagnostics.due(now);
+
+    // The serving cell is read and the cells are measured while registered, before connecting.  A
+    // failure only drops that uplink
+    let link = registration::wait_for_registration().await?;
+    let network_info = if uplinks.network_info_due(now, profile) {
+        match at::get_xmonitor().await {
+            Ok(xmonitor) => Some(xmonitor),
+            Err(e) => {
+                error!("Serving cell read failed: {:?}", defmt::Debug2Format(&e));
+                None
+            }
+        }
+    } else {
+        None
+    };
+    let cells = if uplinks.cell_location_due(now, profile) {
+        match measure_cells().await {
+            Ok(cells) => Some(cells),
+            Err(e) => {
+                error!("Cell measurement failed: {:?}", defmt::Debug2Format(&e));
+                uplinks.cell_location.retry(now, CELL_LOCATION_RETRY_SECS);
+                None
+            }
+        }
+    } else {
+        None
+    };
+    if !diagnostics && network_info.is_none() && cells.is_none() {
+        link.deactivate().await?;
+        return Err(Error::NothingToSend);
+    }
+    let socket = connect().await;
+    link.deactivate().await?;
+    let socket = socket?;
+
+    // Negotiated values are only known while registered
+    if diagnostics {
+        let mut diagnostics = Diagnostics::new(modem_power, now);
+        diagnostics.psm = PsmTimers::granted().await.ok();
+        diagnostics.edrx = EdrxValues::negotiated().await.ok();
+        let last = network_info.is_none() && cells.is_none();
+        post(&socket, ".s/diagnostics", &diagnostics, last).await?;
+        uplinks.diagnostics.sent(now);
+        info!("Diagnostics sent");
+    }
+    if let Some(xmonitor) = network_info {
+        let mut info = NetworkInfo::new(now);
+        info.update(xmonitor);
+        post(&socket, ".s/network", &info, cells.is_none()).await?;
+        uplinks.network_info.sent(now);
+        info!("Network info sent");
+    }
+    if let Some(cells) = cells {
+        let mut location = CellLocation::new(now);
+        location.update(cells);
+        post(&socket, ".s/cells", &location, true).await?;
+        uplinks.cell_location.sent(now);
+        payload.location = location.serving;
+        info!("Cell location sent");
+    }
+
+    close(socket).await
+}
+
+/// Create our DTLS socket
+async fn connect() -> Result<DtlsSocket, Error> {
+    let _energy = energy::active(EnergyState::Connecting);
+
+    // Only try to connect once registered
+    let link = registration::wait_for_registration().await?;
     let socket = DtlsSocket::connect(
         SERVER_URL,
         SERVER_PORT,
         PeerVerification::Enabled,
         &[SECURITY_TAG],
     )
-    .await?;
+    .await;
+    link.deactivate().await?;
+    let socket = socket?;
     info!("DTLS Socket connected");
 
-    let sig_strength = get_signal_strength().await?;
-    payload.signal = sig_strength;
-    info!("Signal Strength: {} dBm", &sig_strength);
+    Ok(socket)
+}
+
+/// Serialize data to JSON, send it as a CoAP POST request to the given path and close the socket
+async fn send<T: Serialize>(socket: DtlsSocket, path: &str, data: &T) -> Result<(), Error> {
+    post(&socket, path, data, true).await?;
+    close(socket).await
+}
 
+/// Serialize data to JSON and send it as a CoAP POST request to the given path, `last` lets the
+/// network release the connection after it
+async fn post<T: Serialize>(
+    socket: &DtlsSocket,
+    path: &str,
+    data: &T,
+    last: bool,
+) -> Result<(), Error> {
     let mut request: CoapRequest<DtlsSocket> = CoapRequest::new();
     // request.message.header.message_id = MESSAGE_ID_COUNTER.fetch_add(1, Ordering::Relaxed);
     request.set_method(RequestType::Post);
-    request.set_path(".s/tank_level");
+    request.set_path(path);
     request
         .message
         .set_content_format(ContentFormat::ApplicationJSON);
-    let json = serde_json::to_vec(payload)?;
+    let json = serde_json::to_vec(data)?;
     // info!("Payload: {:?}", Debug2Format(payload));
     // info!("JSON Byte Vec: {:?}", Debug2Format(&json));
     request.message.payload = json;
 
+    // Let the network release the connection right after the uplink
+    if last {
+        if let Err(e) = rai::release_after_next(socket) {
+            error!("RAI not set: {:?}", defmt::Debug2Format(&e));
+        }
+    }
+
+    let _energy = energy::active(EnergyState::Transmitting);
     socket.send(&request.message.to_bytes()?).await?;
     info!("Payload done");
 
+    Ok(())
+}
+
+/// Close the socket
+async fn close(socket: DtlsSocket) -> Result<(), Error> {
     // The sockets would be dropped after the function call ends, but this explicit call allows them
     // to be dropped asynchronously
     info!("deactivate socket");
@@ -154,24 +462,23 @@ pub async fn transmit_payload(payload: &mut Payload<'_>) -> Result<(), Error> {
     Ok(())
 }
 
-/// Convert sensor ADC value into tank level percentage
+/// Convert sensor ADC value into tank level percentage with the hall effect gauge profile
 pub fn convert_to_tank_level(x: i16) -> u32 {
-    let val = ((534 * x as u32) - 39_0634) / 10000;
-    // info!("Tank Level: {}", &val);
-    if val > 100 {
-        100
-    } else if val < 10 {
-        10
-    } else {
-        val
-    }
+    SensorProfile::HALL_GAUGE.level(x)
 }
 
 /// Convert ADC value into a milli-volt battery measurement
 pub fn convert_to_mv(x: i16) -> u32 {
-    // Stratus: V_bat measurement multiplier = 200/100
-    // Icarus: V_bat measurement multiplier = 147/100
-    ((x * (200 / 100)) as u32 * 3600) / 4096
+    // V_bat measurement multiplier depends on the board's voltage divider
+    let (num, den) = board::VBAT_DIVIDER;
+    (x.max(0) as u32 * num * 3600) / (den * 4096)
+}
+
+/// Convert ADC value into a milli-volt charger input (solar panel) measurement
+#[cfg(feature = "board-icarus")]
+pub fn convert_to_vin_mv(x: i16) -> u32 {
+    let (num, den) = board::VIN_DIVIDER;
+    (x.max(0) as u32 * num * 3600) / (den * 4096)
 }
 
 /// Terminates the application and makes `probe-run` exit with exit-code = 0
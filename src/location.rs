@@ -0,0 +1,69 @@
+//! Cell based location without GNSS.  AT%NCELLMEAS measures the serving and neighbor cells, the
+//! report is sent after boot and then periodically to `.s/cells` where the backend resolves an
+//! approximate position, for install locations and to notice a tank that moved.
+use crate::at::{Ncellmeas, NeighborCell, ServingCell, MAX_NEIGHBORS, NCELLMEAS_MAX_LEN};
+use crate::Error;
+use defmt::info;
+use embassy_time::{with_timeout, Duration};
+use futures::StreamExt;
+use heapless::Vec;
+use nrf_modem::at_notifications::AtNotificationStream;
+use serde::Serialize;
+
+/// Time allowed for the measurement (seconds)
+const MEASUREMENT_TIMEOUT_SECS: u64 = 10;
+
+/// Serving and neighbor cells for the cell based location
+#[derive(Debug, Serialize)]
+pub struct CellLocation {
+    pub serving: Option<ServingCell>,
+    pub neighbors: Vec<NeighborCell, MAX_NEIGHBORS>,
+    pub timestamp: u32,
+}
+
+/// CellLocation constructor
+impl CellLocation {
+    pub fn new(timestamp: u32) -> Self {
+        CellLocation {
+            serving: None,
+            neighbors: Vec::new(),
+            timestamp,
+        }
+    }
+
+    /// Fill in the cells from a measurement
+    pub fn update(&mut self, cells: Ncellmeas) {
+        self.serving = Some(cells.serving);
+        self.neighbors = cells.neighbors;
+    }
+}
+
+/// Measure the serving and neighbor cells, LTE must be active.  The result comes in the
+/// %NCELLMEAS notification, sized for the longest one so the neighbors are not cut off
+pub async fn measure_cells() -> Result<Ncellmeas, Error> {
+    let notifications = AtNotificationStream::<NCELLMEAS_MAX_LEN, 2>::new().await;
+    futures::pin_mut!(notifications);
+    nrf_modem::send_at::<32>("AT%NCELLMEAS").await?;
+
+    let measurement = async {
+        while let Some(notification) = notifications.next().await {
+            if notification.starts_with("%NCELLMEAS:") {
+                // Status other than 0 is a failed or interrupted measurement
+                return Ncellmeas::parse(notification.as_str())?.ok_or(Error::CellMeasurement);
+            }
+        }
+        Err(Error::CellMeasurement)
+    };
+    match with_timeout(Duration::from_secs(MEASUREMENT_TIMEOUT_SECS), measurement).await {
+        Ok(result) => {
+            if let Ok(cells) = &result {
+                info!("Cell measurement: {} neighbor(s)", cells.neighbors.len());
+            }
+            result
+        }
+        Err(e) => {
+            let _ = nrf_modem::send_at::<32>("AT%NCELLMEASSTOP").await;
+            Err(e.into())
+        }
+    }
+}
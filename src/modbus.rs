@@ -0,0 +1,148 @@
+//! Modbus RTU master over an RS-485 transceiver on UARTE1, enabled with the `modbus` cargo
+//! feature.  The configured registers (`config::MODBUS_POINTS`) are polled before each uplink
+//! and sent with the payload.  The frames are encoded and decoded by
+//! `propane_monitor_core::modbus`, which is tested on the host.
+use crate::config::{MODBUS_BAUDRATE, MODBUS_POINTS, MODBUS_POINT_COUNT, MODBUS_TIMEOUT_MS};
+use crate::Error;
+use defmt::{error, Debug2Format};
+use embassy_futures::join::join;
+use embassy_nrf::gpio::{AnyPin, Level, Output, OutputDrive};
+use embassy_nrf::interrupt::UARTE1_SPIM1_SPIS1_TWIM1_TWIS1;
+use embassy_nrf::peripherals::UARTETWISPI1;
+use embassy_nrf::uarte::{self, Parity, Uarte};
+use embassy_time::{with_timeout, Duration, Timer};
+use heapless::Vec;
+use propane_monitor_core::modbus::{
+    decode_read_response, encode_read_request, response_remaining, CRC_LEN, HEADER_LEN,
+};
+pub use propane_monitor_core::modbus::{RegisterFormat, RegisterKind, MAX_REGISTERS};
+use serde::Serialize;
+
+/// A value polled from a Modbus device
+#[derive(Debug)]
+pub struct ModbusPoint {
+    /// Name of the value in the payload
+    pub name: &'static str,
+    pub slave: u8,
+    pub kind: RegisterKind,
+    pub address: u16,
+    pub format: RegisterFormat,
+}
+
+/// Polled value for the payload, `value` is `None` if the device did not answer
+#[derive(Debug, Serialize)]
+pub struct ModbusValue {
+    pub name: &'static str,
+    pub value: Option<i64>,
+}
+
+/// RS-485 transceiver and UARTE, the UARTE is only enabled during a poll
+pub struct ModbusMaster {
+    uarte: UARTETWISPI1,
+    irq: UARTE1_SPIM1_SPIS1_TWIM1_TWIS1,
+    rx: AnyPin,
+    tx: AnyPin,
+    /// Driver enable, tied to /RE so the receiver is off while transmitting
+    de: Output<'static, AnyPin>,
+}
+
+impl ModbusMaster {
+    pub fn new(
+        uarte: UARTETWISPI1,
+        irq: UARTE1_SPIM1_SPIS1_TWIM1_TWIS1,
+        rx: AnyPin,
+        tx: AnyPin,
+        de: AnyPin,
+    ) -> Self {
+        ModbusMaster {
+            uarte,
+            irq,
+            rx,
+            tx,
+            de: Output::new(de, Level::Low, OutputDrive::Standard),
+        }
+    }
+
+    /// Read `count` holding or input registers from a slave
+    pub async fn read_registers(
+        &mut self,
+        slave: u8,
+        kind: RegisterKind,
+        address: u16,
+        count: u16,
+    ) -> Result<Vec<u16, MAX_REGISTERS>, Error> {
+        if count as usize > MAX_REGISTERS {
+            return Err(Error::InvalidFrame);
+        }
+        let request = encode_read_request(slave, kind, address, count);
+
+        // 8 data bits, even parity, 1 stop bit
+        let mut config = uarte::Config::default();
+        config.baudrate = MODBUS_BAUDRATE;
+        config.parity = Parity::INCLUDED;
+        let uarte = Uarte::new(
+            &mut self.uarte,
+            &mut self.irq,
+            &mut self.rx,
+            &mut self.tx,
+            config,
+        );
+        let (mut tx, mut rx) = uarte.split();
+        let de = &mut self.de;
+
+        // Start receiving before transmitting so the start of the response is not missed
+        let mut response = [0; HEADER_LEN + MAX_REGISTERS * 2 + CRC_LEN];
+        let (header, body) = response.split_at_mut(HEADER_LEN);
+        let receive = async {
+            rx.read(header).await?;
+            let remaining = response_remaining(&header.try_into().unwrap());
+            let body = body.get_mut(..remaining).ok_or(Error::InvalidFrame)?;
+            rx.read(body).await?;
+            Ok::<_, Error>(HEADER_LEN + remaining)
+        };
+        let transmit = async {
+            de.set_high();
+            let result = tx.write(&request).await;
+            // The last character is still in the shift register when the DMA completes
+            Timer::after(Duration::from_millis(2)).await;
+            de.set_low();
+            result
+        };
+
+        let timeout = Duration::from_millis(MODBUS_TIMEOUT_MS);
+        let (received, sent) = with_timeout(timeout, join(receive, transmit)).await?;
+        sent?;
+        let len = received?;
+        Ok(decode_read_response(&response[..len], slave, kind, count)?)
+    }
+
+    /// Poll the values of all configured points, a point that fails to read is reported without
+    /// a value
+    pub async fn poll(&mut self) -> Vec<ModbusValue, MODBUS_POINT_COUNT> {
+        let mut values = Vec::new();
+        for point in MODBUS_POINTS.iter() {
+            let value = match self
+                .read_registers(point.slave, point.kind, point.address, point.format.count())
+                .await
+            {
+                Ok(registers) => Some(point.format.value(&registers)),
+                Err(e) => {
+                    error!(
+                        "Modbus read of {} failed: {:?}",
+                        point.name,
+                        Debug2Format(&e)
+                    );
+                    None
+                }
+            };
+            let _ = values.push(ModbusValue {
+                name: point.name,
+                value,
+            });
+
+            // Silent interval of at least 3.5 characters between frames
+            Timer::after(Duration::from_millis(5)).await;
+        }
+        values
+    }
+}
@@ -0,0 +1,64 @@
+//! Serving cell information from AT%XMONITOR, sent after boot and then periodically to
+//! `.s/network`.  Tells which operator, band and cell a device uses when debugging a site.
+use crate::at::XMonitor;
+use defmt::Format;
+use heapless::String;
+use serde::Serialize;
+
+/// Access technology of the serving cell
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Format, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AccessTechnology {
+    LteM,
+    NbIot,
+}
+
+impl AccessTechnology {
+    /// From the AcT value of +CEREG and %XMONITOR
+    fn from_act(act: i32) -> Option<Self> {
+        match act {
+            7 => Some(AccessTechnology::LteM),
+            9 => Some(AccessTechnology::NbIot),
+            _ => None,
+        }
+    }
+}
+
+/// Serving cell information, read while connected to send it
+#[derive(Debug, Serialize)]
+pub struct NetworkInfo {
+    /// Mobile country and network code of the operator
+    pub plmn: Option<String<6>>,
+    pub act: Option<AccessTechnology>,
+    pub band: Option<i32>,
+    /// Tracking area code and E-UTRAN cell ID, hexadecimal
+    pub tac: Option<String<4>>,
+    pub cell_id: Option<String<8>>,
+    pub earfcn: Option<i32>,
+    pub timestamp: u32,
+}
+
+/// NetworkInfo constructor
+impl NetworkInfo {
+    pub fn new(timestamp: u32) -> Self {
+        NetworkInfo {
+            plmn: None,
+            act: None,
+            band: None,
+            tac: None,
+            cell_id: None,
+            earfcn: None,
+            timestamp,
+        }
+    }
+
+    /// Fill in the serving cell from a %XMONITOR response
+    pub fn update(&mut self, monitor: XMonitor) {
+        self.plmn = monitor.plmn;
+        self.act = monitor.act.and_then(AccessTechnology::from_act);
+        self.band = monitor.band;
+        self.tac = monitor.tac;
+        self.cell_id = monitor.cell_id;
+        self.earfcn = monitor.earfcn;
+    }
+}
@@ -0,0 +1,81 @@
+//! Schedule of the uplinks sent besides the tank data.  Each is sent after boot and then every
+//! interval, the ones that are due go out together in one connection, see `transmit_periodic`.
+use crate::config::{
+    CELL_LOCATION_INTERVAL_SECS, DIAGNOSTICS_INTERVAL_SECS, NETWORK_INFO_INTERVAL_SECS,
+};
+use crate::power::PowerProfile;
+
+/// An uplink sent after boot, then every `interval_secs`
+#[derive(Debug, Clone, Copy)]
+pub struct Periodic {
+    interval_secs: u64,
+    next_due: Option<u32>,
+}
+
+impl Periodic {
+    pub const fn new(interval_secs: u64) -> Self {
+        Periodic {
+            interval_secs,
+            next_due: None,
+        }
+    }
+
+    /// Whether the uplink should be sent at `now`
+    pub fn due(&self, now: u32) -> bool {
+        match self.next_due {
+            Some(next) => now >= next,
+            None => true,
+        }
+    }
+
+    /// Record that the uplink was sent at `now`
+    pub fn sent(&mut self, now: u32) {
+        self.next_due = Some(now.saturating_add(self.interval_secs as u32));
+    }
+
+    /// Record a failed attempt at `now`, the uplink is due again after `delay_secs`
+    pub fn retry(&mut self, now: u32, delay_secs: u64) {
+        self.next_due = Some(now.saturating_add(delay_secs as u32));
+    }
+}
+
+/// Schedule of the diagnostics, network info and cell location uplinks
+#[derive(Debug, Clone, Copy)]
+pub struct PeriodicUplinks {
+    pub diagnostics: Periodic,
+    pub network_info: Periodic,
+    pub cell_location: Periodic,
+}
+
+impl PeriodicUplinks {
+    pub const fn new() -> Self {
+        PeriodicUplinks {
+            diagnostics: Periodic::new(DIAGNOSTICS_INTERVAL_SECS),
+            network_info: Periodic::new(NETWORK_INFO_INTERVAL_SECS),
+            cell_location: Periodic::new(CELL_LOCATION_INTERVAL_SECS),
+        }
+    }
+
+    /// Whether the network info uplink should be sent at `now`, if `profile` runs it
+    pub fn network_info_due(&self, now: u32, profile: &PowerProfile) -> bool {
+        profile.network_info && self.network_info.due(now)
+    }
+
+    /// Whether the cell location uplink should be sent at `now`, if `profile` runs it
+    pub fn cell_location_due(&self, now: u32, profile: &PowerProfile) -> bool {
+        profile.cell_location && self.cell_location.due(now)
+    }
+
+    /// Whether any of the uplinks `profile` runs should be sent at `now`
+    pub fn due(&self, now: u32, profile: &PowerProfile) -> bool {
+        self.diagnostics.due(now)
+            || self.network_info_due(now, profile)
+            || self.cell_location_due(now, profile)
+    }
+}
+
+impl Default for PeriodicUplinks {
+    fn default() -> Self {
+        Self::new()
+    }
+}
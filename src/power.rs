@@ -0,0 +1,135 @@
+use crate::at::{set_functional_mode, FunctionalMode};
+use crate::config::{
+    BATCH_SIZE, MODEM_POWER, POWER_CRITICAL_MV, POWER_HYSTERESIS_MV, POWER_SAVER_MV,
+    SAMPLE_INTERVAL_SECS, SAVER_BATCH_SIZE, SAVER_SAMPLE_INTERVAL_SECS,
+};
+use defmt::{info, Format};
+use serde::Serialize;
+
+/// Operating mode selected from the battery voltage
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Format)]
+pub enum PowerMode {
+    Normal,
+    Saver,
+    Critical,
+}
+
+/// Sampling and transmit behavior for a power mode, along with the optional features it runs
+#[derive(Debug, Clone, Copy, Format)]
+pub struct PowerProfile {
+    /// Time between sensor measurements in seconds
+    pub sample_interval: u64,
+    /// Number of samples per uplink
+    pub batch_size: usize,
+    /// Send tamper alerts on motion, `tamper` feature
+    pub tamper: bool,
+    /// Poll the Modbus devices before each uplink, `modbus` feature
+    pub modbus: bool,
+    /// Send the periodic network info uplink
+    pub network_info: bool,
+    /// Measure the neighbor cells and send the periodic cell location uplink
+    pub cell_location: bool,
+    /// Show the device status on the LED until its timeout
+    pub status_led: bool,
+}
+
+impl PowerMode {
+    pub fn profile(&self) -> PowerProfile {
+        match self {
+            PowerMode::Normal => PowerProfile {
+                sample_interval: SAMPLE_INTERVAL_SECS,
+                batch_size: BATCH_SIZE,
+                tamper: true,
+                modbus: true,
+                network_info: true,
+                cell_location: true,
+                status_led: true,
+            },
+            PowerMode::Saver | PowerMode::Critical => PowerProfile {
+                sample_interval: SAVER_SAMPLE_INTERVAL_SECS,
+                batch_size: SAVER_BATCH_SIZE,
+                tamper: false,
+                modbus: false,
+                network_info: false,
+                cell_location: false,
+                status_led: false,
+            },
+        }
+    }
+}
+
+/// Modem power saving profile, selected with `config::MODEM_POWER`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Format, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ModemPower {
+    /// Power saving mode, lowest current but unreachable between uplinks
+    Psm,
+    /// Extended DRX, reachable for downlink once every eDRX cycle
+    Edrx,
+}
+
+impl ModemPower {
+    /// The configured modem power profile
+    pub fn configured() -> Self {
+        MODEM_POWER
+    }
+}
+
+/// Steps the operating mode down as the battery voltage falls through the configured thresholds.
+/// A mode is only left once the voltage recovers `POWER_HYSTERESIS_MV` above its threshold, and
+/// the critical mode is never left.
+pub struct PowerPolicy {
+    mode: PowerMode,
+}
+
+impl PowerPolicy {
+    pub fn new() -> Self {
+        PowerPolicy {
+            mode: PowerMode::Normal,
+        }
+    }
+
+    pub fn mode(&self) -> PowerMode {
+        self.mode
+    }
+
+    /// Update the mode from a load compensated battery voltage, returns the new mode if it changed
+    pub fn update(&mut self, mv: u32) -> Option<PowerMode> {
+        let mode = match self.mode {
+            PowerMode::Critical => PowerMode::Critical,
+            _ if mv < POWER_CRITICAL_MV => PowerMode::Critical,
+            PowerMode::Saver if mv < POWER_SAVER_MV + POWER_HYSTERESIS_MV => PowerMode::Saver,
+            _ if mv < POWER_SAVER_MV => PowerMode::Saver,
+            _ => PowerMode::Normal,
+        };
+
+        if mode == self.mode {
+            return None;
+        }
+        info!("Power mode: {} -> {} ({} mV)", self.mode, mode, mv);
+        self.mode = mode;
+        Some(mode)
+    }
+}
+
+impl Default for PowerPolicy {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Power down the modem and enter System OFF, only a reset or a wake up pin will start the
+/// device again.  The PSM context cannot be kept: leaving System OFF is a reset, and
+/// `nrf_modem::init` boots the modem again after it.  CFUN=0 stores the network information
+/// instead, so the modem attaches without a full band scan after the wake up.
+pub async fn system_off() -> ! {
+    // The modem must be shut down first or it keeps drawing current
+    let _ = set_functional_mode(FunctionalMode::PowerOff).await;
+
+    info!("Entering System OFF");
+    let regulators = unsafe { &*embassy_nrf::pac::REGULATORS::PTR };
+    regulators.systemoff.write(|w| w.systemoff().enable());
+    loop {
+        cortex_m::asm::wfe();
+    }
+}
@@ -1,47 +1,8 @@
+use crate::at::{delete_credential, has_credential, write_credential, CredentialType};
 use crate::config::{PSK, PSK_ID, SECURITY_TAG};
 use crate::Error;
-use core::fmt::write;
-use defmt::Format;
 use heapless::String;
 
-/// Credential Storage Management Types
-#[derive(Clone, Copy, Format)]
-#[allow(dead_code)]
-enum CSMType {
-    RootCert = 0,
-    ClientCert = 1,
-    ClientPrivateKey = 2,
-    Psk = 3,
-    PskId = 4,
-    // ...
-}
-
-/// This function deletes a key or certificate from the nrf modem
-async fn key_delete(ty: CSMType) -> Result<(), Error> {
-    let mut cmd: String<32> = String::new();
-    write(
-        &mut cmd,
-        format_args!("AT%CMNG=3,{},{}", SECURITY_TAG, ty as u32),
-    )
-    .unwrap();
-    nrf_modem::send_at::<32>(cmd.as_str()).await?;
-    Ok(())
-}
-
-/// This function writes a key or certificate to the nrf modem
-async fn key_write(ty: CSMType, data: &str) -> Result<(), Error> {
-    let mut cmd: String<128> = String::new();
-    write(
-        &mut cmd,
-        format_args!(r#"AT%CMNG=0,{},{},"{}""#, SECURITY_TAG, ty as u32, data),
-    )
-    .unwrap();
-
-    nrf_modem::send_at::<128>(&cmd.as_str()).await?;
-
-    Ok(())
-}
-
 /// Delete existing keys/certificates and loads new ones based on config.rs entries
 pub async fn install_psk_id_and_psk() -> Result<(), Error> {
     assert!(
@@ -49,11 +10,15 @@ pub async fn install_psk_id_and_psk() -> Result<(), Error> {
         "PSK ID and PSK must not be empty. Set them in the `config` module."
     );
 
-    key_delete(CSMType::PskId).await?;
-    key_delete(CSMType::Psk).await?;
+    // Deleting a credential that is not stored is an error
+    for ty in [CredentialType::PskId, CredentialType::Psk] {
+        if has_credential(SECURITY_TAG, ty).await? {
+            delete_credential(SECURITY_TAG, ty).await?;
+        }
+    }
 
-    key_write(CSMType::PskId, &PSK_ID).await?;
-    key_write(CSMType::Psk, &encode_psk_as_hex(&PSK)).await?;
+    write_credential(SECURITY_TAG, CredentialType::PskId, &PSK_ID).await?;
+    write_credential(SECURITY_TAG, CredentialType::Psk, &encode_psk_as_hex(&PSK)).await?;
 
     Ok(())
 }
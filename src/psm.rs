@@ -0,0 +1,88 @@
+//! LTE power saving mode (PSM).  The requested periodic TAU (T3412) and active time (T3324) are
+//! only a request, the network decides what is granted.  The granted values are read from +CEREG
+//! on each uplink and the uplink schedule is aligned with them.
+use crate::at::{get_psm_timers, set_psm, GprsTimer};
+use crate::config::{PSM_ACTIVE_TIME_SECS, PSM_PERIODIC_TAU_SECS};
+use crate::power::PowerProfile;
+use crate::Error;
+use defmt::{info, Format};
+use serde::Serialize;
+
+/// Network granted PSM timers in seconds, `None` when deactivated or not granted
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Format, Serialize)]
+pub struct PsmTimers {
+    pub active_time: Option<u32>,
+    pub periodic_tau: Option<u32>,
+}
+
+impl PsmTimers {
+    /// Read the granted timers, the modem must be registered
+    pub async fn granted() -> Result<Self, Error> {
+        let (active_time, periodic_tau) = get_psm_timers().await?;
+        Ok(PsmTimers {
+            active_time: active_time.and_then(|t| GprsTimer::ActiveTime.decode(t)),
+            periodic_tau: periodic_tau.and_then(|t| GprsTimer::PeriodicTau.decode(t)),
+        })
+    }
+
+    /// The network granted PSM
+    pub fn enabled(&self) -> bool {
+        self.active_time.is_some() && self.periodic_tau.is_some()
+    }
+}
+
+/// Requests PSM and keeps track of what the network granted
+pub struct PsmManager {
+    granted: Option<PsmTimers>,
+}
+
+impl PsmManager {
+    pub fn new() -> Self {
+        PsmManager { granted: None }
+    }
+
+    /// Request the configured PSM timers.  The granted values are reported in the +CEREG
+    /// notifications enabled by the `registration` tracker
+    pub async fn request(&self) -> Result<(), Error> {
+        let tau = GprsTimer::PeriodicTau.encode(PSM_PERIODIC_TAU_SECS);
+        let active = GprsTimer::ActiveTime.encode(PSM_ACTIVE_TIME_SECS);
+
+        set_psm(Some((tau, active))).await?;
+        info!(
+            "PSM requested: TAU {} s, active time {} s",
+            PSM_PERIODIC_TAU_SECS, PSM_ACTIVE_TIME_SECS
+        );
+        Ok(())
+    }
+
+    /// Record the granted timers read during an uplink, returns true if they changed
+    pub fn update(&mut self, timers: Option<PsmTimers>) -> bool {
+        if timers.is_none() || timers == self.granted {
+            return false;
+        }
+        info!("PSM granted: {}", timers);
+        self.granted = timers;
+        true
+    }
+
+    /// Keep uplinks no further apart than the granted periodic TAU, so the modem wakes up to send
+    /// data instead of for an extra tracking area update
+    pub fn align(&self, profile: PowerProfile) -> PowerProfile {
+        match self.granted.and_then(|t| t.periodic_tau) {
+            Some(tau) => {
+                let max_batch = (tau as u64 / profile.sample_interval).max(1) as usize;
+                PowerProfile {
+                    batch_size: profile.batch_size.min(max_batch),
+                    ..profile
+                }
+            }
+            None => profile,
+        }
+    }
+}
+
+impl Default for PsmManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
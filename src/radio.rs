@@ -0,0 +1,130 @@
+//! Radio quality metrics.  RSRP and RSRQ are read with AT+CESQ and SNR with AT%XSNRSQ?, the
+//! modem only measures the serving cell while LTE is on, so they are read during each uplink and
+//! event connection.  Unknown readings are counted instead of being mixed into the values.  Each
+//! batch window reports min/avg/max of each metric.
+use crate::at::{get_cesq, get_snr};
+use crate::Error;
+use defmt::Format;
+use serde::ser::SerializeStruct;
+use serde::{Serialize, Serializer};
+
+/// One reading of the radio metrics, `None` when the modem does not know the value
+#[derive(Debug, Clone, Copy, PartialEq, Format)]
+pub struct RadioQuality {
+    /// Reference signal received power (dBm)
+    pub rsrp: Option<i32>,
+    /// Reference signal received quality (dB)
+    pub rsrq: Option<f32>,
+    /// Signal to noise ratio (dB)
+    pub snr: Option<i32>,
+}
+
+impl RadioQuality {
+    pub async fn read() -> Result<Self, Error> {
+        let cesq = get_cesq().await?;
+        Ok(RadioQuality {
+            rsrp: cesq.rsrp_dbm(),
+            rsrq: cesq.rsrq_db(),
+            snr: get_snr().await?,
+        })
+    }
+}
+
+/// Min/avg/max of a metric over the batch window, serialized with the number of unknown readings.
+/// Min/avg/max are null when no reading was known
+#[derive(Debug, Clone, Copy)]
+pub struct Metric {
+    min: f32,
+    max: f32,
+    sum: f32,
+    count: u16,
+    unknown: u16,
+}
+
+impl Metric {
+    pub fn new() -> Self {
+        Metric {
+            min: f32::MAX,
+            max: f32::MIN,
+            sum: 0.0,
+            count: 0,
+            unknown: 0,
+        }
+    }
+
+    pub fn add(&mut self, value: Option<f32>) {
+        match value {
+            Some(value) => {
+                self.min = self.min.min(value);
+                self.max = self.max.max(value);
+                self.sum += value;
+                self.count += 1;
+            }
+            None => self.unknown += 1,
+        }
+    }
+
+    pub fn min(&self) -> Option<f32> {
+        (self.count > 0).then(|| self.min)
+    }
+
+    pub fn avg(&self) -> Option<f32> {
+        (self.count > 0).then(|| self.sum / self.count as f32)
+    }
+
+    pub fn max(&self) -> Option<f32> {
+        (self.count > 0).then(|| self.max)
+    }
+}
+
+impl Default for Metric {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Serialize for Metric {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("Metric", 4)?;
+        state.serialize_field("min", &self.min())?;
+        state.serialize_field("avg", &self.avg())?;
+        state.serialize_field("max", &self.max())?;
+        state.serialize_field("unknown", &self.unknown)?;
+        state.end()
+    }
+}
+
+/// Radio metrics of a batch window
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct RadioStats {
+    pub rsrp: Metric,
+    pub rsrq: Metric,
+    pub snr: Metric,
+}
+
+impl RadioStats {
+    pub fn new() -> Self {
+        RadioStats {
+            rsrp: Metric::new(),
+            rsrq: Metric::new(),
+            snr: Metric::new(),
+        }
+    }
+
+    pub fn add(&mut self, quality: RadioQuality) {
+        self.rsrp.add(quality.rsrp.map(|v| v as f32));
+        self.rsrq.add(quality.rsrq);
+        self.snr.add(quality.snr.map(|v| v as f32));
+    }
+
+    /// Start a new batch window
+    pub fn clear(&mut self) {
+        *self = Self::new();
+    }
+}
+
+impl Default for RadioStats {
+    fn default() -> Self {
+        Self::new()
+    }
+}
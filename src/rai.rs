@@ -0,0 +1,69 @@
+//! Release Assistance Indication (RAI).  Without it the modem stays in RRC connected state after
+//! the uplink until the network's inactivity timer expires, often 5 to 20 s of radio on time per
+//! batch.  Access stratum RAI is enabled once with AT%RAI=1, then the `SO_RAI` socket option on
+//! the last datagram of a connection tells the modem it may release the connection.  Not all
+//! modem firmware supports RAI, so a failure in either step is logged and only costs the energy
+//! saving.
+use crate::config::RELEASE_ASSISTANCE;
+use crate::Error;
+use core::ffi::c_void;
+use core::mem::size_of;
+use defmt::Format;
+use nrf_modem::DtlsSocket;
+
+/// Socket option level and `SO_RAI` option, from nrf_socket.h
+const NRF_SOL_SOCKET: i32 = 1;
+const NRF_SO_RAI: i32 = 61;
+
+/// Release Assistance Indication sent with the last uplink of a connection
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Format)]
+pub enum ReleaseAssistance {
+    /// Stay connected until the network releases the connection
+    Disabled,
+    /// Release right after the uplink, no response is expected
+    NoResponse,
+    /// Release after a single downlink, e.g. the ACK of a confirmable CoAP request
+    SingleResponse,
+}
+
+impl ReleaseAssistance {
+    /// `SO_RAI` value, `NRF_RAI_LAST` or `NRF_RAI_ONE_RESP`
+    fn value(&self) -> i32 {
+        match self {
+            ReleaseAssistance::Disabled => 0,
+            ReleaseAssistance::NoResponse => 2,
+            ReleaseAssistance::SingleResponse => 3,
+        }
+    }
+}
+
+/// Enable access stratum RAI, only accepted while the modem is offline (AT+CFUN=0)
+pub async fn enable() -> Result<(), Error> {
+    if RELEASE_ASSISTANCE == ReleaseAssistance::Disabled {
+        return Ok(());
+    }
+    nrf_modem::send_at::<32>("AT%RAI=1").await?;
+    Ok(())
+}
+
+/// Indicate that the next datagram sent on `socket` is the last of the connection
+pub fn release_after_next(socket: &DtlsSocket) -> Result<(), Error> {
+    if RELEASE_ASSISTANCE == ReleaseAssistance::Disabled {
+        return Ok(());
+    }
+    let value = RELEASE_ASSISTANCE.value();
+    // Safety: the option value outlives the call and its length is passed along
+    let result = unsafe {
+        nrfxlib_sys::nrf_setsockopt(
+            socket.as_raw_fd(),
+            NRF_SOL_SOCKET,
+            NRF_SO_RAI,
+            &value as *const i32 as *const c_void,
+            size_of::<i32>() as u32,
+        )
+    };
+    match result {
+        0 => Ok(()),
+        e => Err(Error::SocketOption(e)),
+    }
+}
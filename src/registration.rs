@@ -0,0 +1,157 @@
+//! Network registration tracking.  The +CEREG notifications keep the registration status up to
+//! date, so an uplink waits for registration and fails with a specific error when registration is
+//! denied or there is no network, instead of running into the transmit timeout with the radio on.
+use crate::at::{get_cereg, Cereg, RegistrationStatus};
+use crate::config::{
+    FIRST_REGISTRATION_TIMEOUT_SECS, FIRST_UPLINK_TIMEOUT_SECS, REGISTRATION_TIMEOUT_SECS,
+    UPLINK_TIMEOUT_SECS,
+};
+use crate::edrx;
+use crate::status::{set_status, LedStatus};
+use crate::Error;
+use core::cell::Cell;
+use core::sync::atomic::{AtomicBool, Ordering};
+use defmt::{error, info};
+use embassy_futures::select::select;
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::blocking_mutex::Mutex;
+use embassy_sync::signal::Signal;
+use embassy_time::{with_timeout, Duration, Timer};
+use futures::StreamExt;
+use nrf_modem::at_notifications::AtNotificationStream;
+use nrf_modem::LteLink;
+
+/// Status is also read with AT+CEREG? this often, in case a notification was missed or the
+/// tracker is not running
+const POLL_SECS: u64 = 10;
+
+static STATUS: Mutex<CriticalSectionRawMutex, Cell<Option<RegistrationStatus>>> =
+    Mutex::new(Cell::new(None));
+static CHANGED: Signal<CriticalSectionRawMutex, RegistrationStatus> = Signal::new();
+/// Registered at least once since power up, the first attach is allowed to take longer.  Kept
+/// through System OFF, see `restore_attached`
+static ATTACHED: AtomicBool = AtomicBool::new(false);
+
+/// Last known registration status, `None` before the modem reported one
+pub fn status() -> Option<RegistrationStatus> {
+    STATUS.lock(|status| status.get())
+}
+
+/// Whether the modem registered at least once since power up
+pub fn attached() -> bool {
+    ATTACHED.load(Ordering::Relaxed)
+}
+
+/// Restore the flag saved before System OFF, the modem keeps what it learned about the network
+/// so attaching after a wake up is not a cold attach
+pub fn restore_attached(attached: bool) {
+    ATTACHED.store(attached, Ordering::Relaxed);
+}
+
+/// Time allowed for an uplink, including the registration.  Longer until the first attach
+pub fn uplink_timeout() -> Duration {
+    Duration::from_secs(if attached() {
+        UPLINK_TIMEOUT_SECS
+    } else {
+        FIRST_UPLINK_TIMEOUT_SECS
+    })
+}
+
+fn update(new: RegistrationStatus) {
+    let old = STATUS.lock(|status| status.replace(Some(new)));
+    if new.is_registered() {
+        ATTACHED.store(true, Ordering::Relaxed);
+    }
+    if old != Some(new) {
+        info!("Registration: {}", new);
+        CHANGED.signal(new);
+        show_status();
+    }
+}
+
+/// Show the registration status on the status LED, nothing changes while LTE is off
+pub fn show_status() {
+    let led = match status() {
+        Some(status) if status.is_registered() => LedStatus::Registered,
+        Some(RegistrationStatus::Searching) => LedStatus::Searching,
+        Some(RegistrationStatus::Denied | RegistrationStatus::SimFailure) => LedStatus::Error,
+        _ => return,
+    };
+    set_status(led);
+}
+
+/// Follows the +CEREG notifications, run it from a task with `run`.  The +CEDRXP notifications
+/// enabled by `edrx::request_edrx` are handed to `edrx::notified`
+pub struct RegistrationTracker {
+    notifications: AtNotificationStream<128, 4>,
+}
+
+impl RegistrationTracker {
+    /// Subscribe to the notifications and enable them with the most detail, +CEREG=5
+    pub async fn new() -> Result<Self, Error> {
+        let notifications = AtNotificationStream::new().await;
+        nrf_modem::send_at::<32>("AT+CEREG=5").await?;
+        Ok(RegistrationTracker { notifications })
+    }
+
+    pub async fn run(self) {
+        let notifications = self.notifications;
+        futures::pin_mut!(notifications);
+        while let Some(notification) = notifications.next().await {
+            if notification.starts_with("+CEDRXP:") {
+                edrx::notified(notification.as_str());
+                continue;
+            }
+            if !notification.starts_with("+CEREG:") {
+                continue;
+            }
+            match Cereg::parse_notification(notification.as_str()) {
+                Ok(cereg) => update(cereg.stat),
+                Err(e) => error!("Bad +CEREG notification: {:?}", defmt::Debug2Format(&e)),
+            }
+        }
+    }
+}
+
+/// Activate LTE and wait until the modem is registered, home or roaming.  Fails with
+/// `Error::RegistrationDenied` when the network rejects the device and `Error::NoService` when
+/// no network is found within `REGISTRATION_TIMEOUT_SECS`, or `FIRST_REGISTRATION_TIMEOUT_SECS`
+/// until the modem registered once.  LTE stays active while the returned link is held,
+/// deactivate it once the socket is connected
+pub async fn wait_for_registration() -> Result<LteLink, Error> {
+    let timeout = Duration::from_secs(if attached() {
+        REGISTRATION_TIMEOUT_SECS
+    } else {
+        FIRST_REGISTRATION_TIMEOUT_SECS
+    });
+    wait_for_registration_within(timeout).await
+}
+
+/// `wait_for_registration` with its own timeout, e.g. for a SIM that was just selected
+pub async fn wait_for_registration_within(timeout: Duration) -> Result<LteLink, Error> {
+    let link = LteLink::new().await?;
+    let result = match with_timeout(timeout, registered()).await {
+        Ok(result) => result,
+        Err(_) => Err(Error::NoService),
+    };
+    match result {
+        Ok(()) => Ok(link),
+        Err(e) => {
+            link.deactivate().await?;
+            Err(e)
+        }
+    }
+}
+
+async fn registered() -> Result<(), Error> {
+    loop {
+        update(get_cereg().await?.stat);
+        match status() {
+            Some(status) if status.is_registered() => return Ok(()),
+            Some(RegistrationStatus::Denied) => return Err(Error::RegistrationDenied),
+            Some(RegistrationStatus::SimFailure) => return Err(Error::NoService),
+            _ => {}
+        }
+        select(CHANGED.wait(), Timer::after(Duration::from_secs(POLL_SECS))).await;
+    }
+}
@@ -0,0 +1,176 @@
+//! Tank sensor drivers.  The measurement sequence and the `Sensor` trait live in
+//! `propane_monitor_core::sensor`, which is tested on the host with a mock ADC.  This module
+//! provides the SAADC and timer behind it and the drivers for the sensors on the board.
+use crate::config::{SENSOR_FAULTS, TANKS, TANK_COUNT};
+use crate::tank::{PressureProfile, SensorKind, SensorProfile};
+#[cfg(feature = "ultrasonic")]
+use crate::ultrasonic::UltrasonicSensor;
+use alloc::boxed::Box;
+use embassy_nrf::gpio::{AnyPin, Output};
+use embassy_nrf::saadc::Saadc;
+use embassy_time::{Duration, Timer};
+use embedded_hal::digital::v2::OutputPin;
+use heapless::Vec;
+use propane_monitor_core::fault::FaultDetector;
+pub use propane_monitor_core::sensor::{measure, Adc, Delay, Measurement, Sensor, SensorFault};
+
+/// The SAADC as a sample source for `measure`
+pub struct SaadcAdc<'a, 'd, const N: usize>(pub &'a mut Saadc<'d, N>);
+
+impl<const N: usize> Adc for SaadcAdc<'_, '_, N> {
+    /// Sample all channels and return the raw value of `channel`.  A conversion takes a few tens of
+    /// microseconds, so it is not worth yielding for
+    fn sample(&mut self, channel: usize) -> i16 {
+        let mut buf = [0; N];
+        self.0.blocking_sample(&mut buf);
+        buf[channel]
+    }
+}
+
+/// Settle delay for `measure` on the embassy timer
+pub struct TimerDelay;
+
+impl Delay for TimerDelay {
+    type Future = Timer;
+
+    fn delay_us(&mut self, us: u32) -> Timer {
+        Timer::after(Duration::from_micros(us as u64))
+    }
+}
+
+/// Hall effect float gauge on a SAADC channel with a switched power pin
+pub struct HallEffectGauge<P> {
+    channel: usize,
+    power: P,
+    profile: SensorProfile,
+    faults: FaultDetector,
+}
+
+impl<P: OutputPin> HallEffectGauge<P> {
+    pub fn new(channel: usize, power: P, profile: SensorProfile) -> Self {
+        HallEffectGauge {
+            channel,
+            power,
+            profile,
+            faults: FaultDetector::new(SENSOR_FAULTS, profile.cal_min, profile.cal_max),
+        }
+    }
+}
+
+impl<P: OutputPin> Sensor for HallEffectGauge<P> {
+    /// The input is pulled down, see `board::SensorPins::split`
+    fn read_idle(&mut self, adc: &mut dyn Adc) -> Option<i16> {
+        Some(adc.sample(self.channel))
+    }
+
+    fn power_up(&mut self) {
+        let _ = self.power.set_high();
+    }
+
+    /// Max power on time = 330us (wait for 500us to be safe)
+    fn settle_us(&self) -> u32 {
+        500
+    }
+
+    fn read(&mut self, adc: &mut dyn Adc) -> Option<i16> {
+        Some(adc.sample(self.channel))
+    }
+
+    fn power_down(&mut self) {
+        let _ = self.power.set_low();
+    }
+
+    fn level(&self, raw: i16) -> Option<u32> {
+        Some(self.profile.level(raw))
+    }
+
+    fn check_fault(&mut self, idle: Option<i16>, raw: i16) -> SensorFault {
+        self.faults.classify(idle, raw)
+    }
+}
+
+/// Current loop or ratiometric pressure transducer on a SAADC channel, `power` switches the
+/// transducer supply (or its loop supply)
+pub struct PressureTransducer<P> {
+    channel: usize,
+    power: P,
+    profile: PressureProfile,
+}
+
+impl<P: OutputPin> PressureTransducer<P> {
+    pub fn new(channel: usize, power: P, profile: PressureProfile) -> Self {
+        PressureTransducer {
+            channel,
+            power,
+            profile,
+        }
+    }
+}
+
+impl<P: OutputPin> Sensor for PressureTransducer<P> {
+    fn power_up(&mut self) {
+        let _ = self.power.set_high();
+    }
+
+    fn settle_us(&self) -> u32 {
+        self.profile.settle_ms as u32 * 1000
+    }
+
+    fn read(&mut self, adc: &mut dyn Adc) -> Option<i16> {
+        Some(adc.sample(self.channel))
+    }
+
+    fn power_down(&mut self) {
+        let _ = self.power.set_low();
+    }
+
+    fn level(&self, raw: i16) -> Option<u32> {
+        self.profile.level(self.profile.pressure(raw))
+    }
+
+    fn pressure(&self, raw: i16) -> Option<u32> {
+        Some(self.profile.pressure(raw))
+    }
+
+    /// A live zero output means a broken wire reads as an open circuit
+    fn check_fault(&mut self, _idle: Option<i16>, raw: i16) -> SensorFault {
+        if raw <= self.profile.open_max {
+            SensorFault::OpenCircuit
+        } else if raw >= self.profile.short_min {
+            SensorFault::Short
+        } else {
+            SensorFault::None
+        }
+    }
+}
+
+/// Sensor drivers for all configured tanks, in the order of `config::TANKS`.  `power` holds the
+/// board's sensor power pins by channel, see `board::SensorPins::split`.  The ultrasonic tank
+/// needs `ultrasonic::receive` running
+pub fn tank_sensors<const N: usize>(
+    mut power: [Option<Output<'static, AnyPin>>; N],
+) -> Vec<Box<dyn Sensor>, TANK_COUNT> {
+    #[cfg(feature = "ultrasonic")]
+    let mut ultrasonic = false;
+    TANKS
+        .iter()
+        .enumerate()
+        .map(|(adc_channel, tank)| -> Box<dyn Sensor> {
+            let power = power[tank.channel].take().expect("tank channel used twice");
+            match tank.sensor {
+                SensorKind::HallEffect(profile) => {
+                    Box::new(HallEffectGauge::new(adc_channel, power, profile))
+                }
+                SensorKind::Pressure(profile) => {
+                    Box::new(PressureTransducer::new(adc_channel, power, profile))
+                }
+                #[cfg(feature = "ultrasonic")]
+                SensorKind::Ultrasonic(profile) => {
+                    assert!(!ultrasonic, "only one ultrasonic tank is supported");
+                    ultrasonic = true;
+                    Box::new(UltrasonicSensor::new(power, profile))
+                }
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,96 @@
+//! Icarus SIM management: the SIM is selected while the modem is offline, and the other SIM is
+//! used if the selected one does not register to the network in time.
+use crate::at::{get_iccid, set_functional_mode, FunctionalMode, ICCID_LEN};
+use crate::board::{Board, Sim};
+use crate::config::{PREFERRED_SIM, SIM_FALLBACK_TIMEOUTS, SIM_REGISTRATION_TIMEOUT_SECS};
+use crate::registration::wait_for_registration_within;
+use crate::Error;
+use defmt::{error, info};
+use embassy_time::{Duration, Timer};
+use heapless::String;
+use serde::Serialize;
+
+/// Active SIM, reported in the payload
+#[derive(Debug, Clone, Serialize)]
+pub struct SimStatus {
+    pub sim: Sim,
+    pub iccid: String<ICCID_LEN>,
+}
+
+/// Selects the SIM and falls back to the other one when it fails to register
+pub struct SimManager {
+    active: Sim,
+}
+
+impl SimManager {
+    pub fn new() -> Self {
+        SimManager {
+            active: PREFERRED_SIM,
+        }
+    }
+
+    /// The selected SIM
+    pub fn active(&self) -> Sim {
+        self.active
+    }
+
+    /// Time to try the other SIM, after every `SIM_FALLBACK_TIMEOUTS` consecutive failed uplinks
+    pub fn needs_fallback(&self, timeouts: u8) -> bool {
+        timeouts > 0 && timeouts % SIM_FALLBACK_TIMEOUTS == 0
+    }
+
+    /// Register with the preferred SIM, or the other SIM if it fails
+    pub async fn start(&mut self, board: &mut Board) -> Result<SimStatus, Error> {
+        self.select(board, PREFERRED_SIM).await
+    }
+
+    /// Switch to the other SIM, e.g. after repeated failed uplinks.  Switches back if the other
+    /// SIM does not register either
+    pub async fn fallback(&mut self, board: &mut Board) -> Result<SimStatus, Error> {
+        self.select(board, self.active.other()).await
+    }
+
+    /// Try `first`, then the other SIM
+    async fn select(&mut self, board: &mut Board, first: Sim) -> Result<SimStatus, Error> {
+        for sim in [first, first.other()] {
+            if self.register(board, sim).await? {
+                let status = SimStatus {
+                    sim,
+                    iccid: get_iccid().await?,
+                };
+                info!("SIM {} registered, ICCID: {}", sim, status.iccid.as_str());
+                return Ok(status);
+            }
+            error!("SIM {} did not register", sim);
+        }
+        Err(Error::NotRegistered)
+    }
+
+    /// Switch to `sim` and wait for it to register, returns false when it is denied or does not
+    /// register in time
+    async fn register(&mut self, board: &mut Board, sim: Sim) -> Result<bool, Error> {
+        info!("Selecting SIM {}", sim);
+        // The SIM can only be switched while the modem is offline
+        set_functional_mode(FunctionalMode::PowerOff).await?;
+        board.select_sim(sim);
+        self.active = sim;
+        Timer::after(Duration::from_millis(100)).await;
+
+        // The link turns LTE back on, the registration is followed from the +CEREG notifications
+        let timeout = Duration::from_secs(SIM_REGISTRATION_TIMEOUT_SECS);
+        match wait_for_registration_within(timeout).await {
+            Ok(link) => {
+                link.deactivate().await?;
+                Ok(true)
+            }
+            Err(Error::NoService | Error::RegistrationDenied) => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+impl Default for SimManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
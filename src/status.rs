@@ -0,0 +1,140 @@
+//! Status LED service, shows what the device is doing so installers can check a unit without a
+//! laptop.  The LED is driven with PWM from a task, the application sets the status with
+//! `set_status` and the registration tracker shows searching and registered.  Each status is
+//! shown for at least one full pattern so short ones, like the ADC calibration, can be seen.  On
+//! the Icarus RGB LED each status has its own color, the Stratus only has the blue LED so the
+//! statuses are told apart by their blink pattern.  The LED is turned off for good
+//! `STATUS_LED_TIMEOUT_SECS` after boot to save power, or earlier with `turn_off`.
+use crate::config::STATUS_LED_TIMEOUT_SECS;
+use defmt::{info, Format};
+use embassy_futures::select::{select, select3, Either3};
+use embassy_nrf::gpio::AnyPin;
+use embassy_nrf::peripherals::PWM0;
+use embassy_nrf::pwm::{Prescaler, SimplePwm};
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::signal::Signal;
+use embassy_time::{Duration, Instant, Timer};
+
+/// PWM top value, 1 MHz / 255 = ~4 kHz
+const MAX_DUTY: u16 = 255;
+
+/// Device status shown on the LED
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Format)]
+pub enum LedStatus {
+    Booting,
+    Searching,
+    Registered,
+    Transmitting,
+    Error,
+    LowBattery,
+    Calibration,
+}
+
+impl LedStatus {
+    /// LED color as (red, green, blue)
+    fn color(&self) -> (u8, u8, u8) {
+        match self {
+            LedStatus::Booting => (255, 255, 255),
+            LedStatus::Searching => (255, 160, 0),
+            LedStatus::Registered => (0, 255, 0),
+            LedStatus::Transmitting => (0, 0, 255),
+            LedStatus::Error => (255, 0, 0),
+            LedStatus::LowBattery => (255, 64, 0),
+            LedStatus::Calibration => (255, 0, 255),
+        }
+    }
+
+    /// Blink pattern as alternating on and off times (ms), repeated
+    fn pattern(&self) -> &'static [u64] {
+        match self {
+            LedStatus::Booting => &[1000],
+            LedStatus::Searching => &[500, 500],
+            LedStatus::Registered => &[100, 2900],
+            LedStatus::Transmitting => &[100, 100],
+            LedStatus::Error => &[100, 150, 100, 150, 100, 1400],
+            LedStatus::LowBattery => &[100, 150, 100, 1650],
+            LedStatus::Calibration => &[1500, 500],
+        }
+    }
+}
+
+static STATUS: Signal<CriticalSectionRawMutex, LedStatus> = Signal::new();
+static OFF: Signal<CriticalSectionRawMutex, ()> = Signal::new();
+
+/// Show a new status on the LED
+pub fn set_status(status: LedStatus) {
+    STATUS.signal(status);
+}
+
+/// Turn the LED off for good before its timeout
+pub fn turn_off() {
+    OFF.signal(());
+}
+
+/// The status LED, run it from a task with `run`
+pub struct StatusLed {
+    pwm: SimplePwm<'static, PWM0>,
+    rgb: bool,
+}
+
+impl StatusLed {
+    /// Use the RGB LED when `red` and `green` are fitted, otherwise only the blue LED
+    pub fn new(pwm: PWM0, red: Option<AnyPin>, green: Option<AnyPin>, blue: AnyPin) -> Self {
+        let (pwm, rgb) = match (red, green) {
+            (Some(red), Some(green)) => (SimplePwm::new_3ch(pwm, red, green, blue), true),
+            _ => (SimplePwm::new_1ch(pwm, blue), false),
+        };
+        pwm.set_prescaler(Prescaler::Div16);
+        pwm.set_max_duty(MAX_DUTY);
+
+        let mut led = StatusLed { pwm, rgb };
+        led.show(None);
+        led
+    }
+
+    /// Show the status patterns until the LED timeout or `turn_off`
+    pub async fn run(mut self) {
+        let timeout = Instant::now() + Duration::from_secs(STATUS_LED_TIMEOUT_SECS);
+        let mut status = LedStatus::Booting;
+        loop {
+            let pattern = status.pattern();
+            let color = status.color();
+            let blink = async {
+                loop {
+                    for (i, ms) in pattern.iter().enumerate() {
+                        self.show((i % 2 == 0).then_some(color));
+                        Timer::after(Duration::from_millis(*ms)).await;
+                    }
+                }
+            };
+            let next = async {
+                Timer::after(Duration::from_millis(pattern.iter().sum())).await;
+                STATUS.wait().await
+            };
+
+            match select3(next, blink, select(Timer::at(timeout), OFF.wait())).await {
+                Either3::First(new) => status = new,
+                Either3::Second(_) => unreachable!(),
+                Either3::Third(_) => break,
+            }
+        }
+
+        info!("Status LED off");
+        self.show(None);
+        // Dropping the PWM stops it and disconnects the pins
+    }
+
+    /// Turn the LED on with a color, or off with `None`
+    fn show(&mut self, color: Option<(u8, u8, u8)>) {
+        let (red, green, blue) = color.unwrap_or_default();
+        if self.rgb {
+            self.pwm.set_duty(0, red as u16);
+            self.pwm.set_duty(1, green as u16);
+            self.pwm.set_duty(2, blue as u16);
+        } else {
+            // Any color lights the blue LED at full brightness
+            let on = color.is_some();
+            self.pwm.set_duty(0, if on { MAX_DUTY } else { 0 });
+        }
+    }
+}
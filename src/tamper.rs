@@ -0,0 +1,168 @@
+//! Tamper and tilt detection with the Stratus LIS2DH12 accelerometer, enabled with the `tamper`
+//! cargo feature.  The accelerometer runs in 1 Hz low power mode (~4 uA) and raises INT1 on
+//! motion, the orientation is then compared with the one seen when the device was installed.
+use crate::config::{TAMPER_HOLDOFF_SECS, TAMPER_MOTION_MG, TAMPER_REMOVED_COS, TAMPER_TILT_COS};
+use crate::Error;
+use defmt::{info, Format};
+use embassy_nrf::gpio::{AnyPin, Input, Pull};
+use embassy_nrf::twim::{Instance, Twim};
+use embassy_time::{Duration, Instant};
+use serde::Serialize;
+
+/// LIS2DH12 I2C address, SA0 is pulled high on the Stratus
+const ADDRESS: u8 = 0x19;
+const WHO_AM_I: u8 = 0x0F;
+const WHO_AM_I_VALUE: u8 = 0x33;
+const CTRL_REG1: u8 = 0x20;
+const CTRL_REG2: u8 = 0x21;
+const CTRL_REG3: u8 = 0x22;
+const CTRL_REG4: u8 = 0x23;
+const CTRL_REG5: u8 = 0x24;
+const OUT_X_L: u8 = 0x28;
+const INT1_CFG: u8 = 0x30;
+const INT1_SRC: u8 = 0x31;
+const INT1_THS: u8 = 0x32;
+const INT1_DURATION: u8 = 0x33;
+/// Set on a register address to auto increment over a multi byte read
+const AUTO_INCREMENT: u8 = 0x80;
+/// Low power mode, +-2 g: 8 bit samples of 16 mg
+const MG_PER_LSB: i16 = 16;
+
+/// Kind of tamper detected, from least to most severe
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Format, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TamperKind {
+    /// Device moved or was knocked, orientation unchanged
+    Movement,
+    /// Tank is tilted more than `TAMPER_TILT_COS` from its installed orientation
+    Tilt,
+    /// Device is tilted more than `TAMPER_REMOVED_COS`, taken off the tank or tank knocked over
+    Removed,
+}
+
+/// LIS2DH12 accelerometer in motion wake mode
+pub struct Accelerometer<'d, T: Instance> {
+    twim: Twim<'d, T>,
+    int: Input<'d, AnyPin>,
+}
+
+impl<'d, T: Instance> Accelerometer<'d, T> {
+    /// Check the accelerometer responds and configure it for motion wake up on INT1
+    pub async fn new(twim: Twim<'d, T>, int: AnyPin) -> Result<Self, Error> {
+        let mut accel = Accelerometer {
+            twim,
+            int: Input::new(int, Pull::None),
+        };
+
+        let id = accel.read_register(WHO_AM_I).await?;
+        if id != WHO_AM_I_VALUE {
+            return Err(Error::Accelerometer(id));
+        }
+
+        // 1 Hz, low power mode, X/Y/Z enabled
+        accel.write_register(CTRL_REG1, 0x1F).await?;
+        // High pass filter on INT1 so only changes in acceleration wake us, not gravity
+        accel.write_register(CTRL_REG2, 0x01).await?;
+        // IA1 interrupt on INT1
+        accel.write_register(CTRL_REG3, 0x40).await?;
+        // +-2 g full scale
+        accel.write_register(CTRL_REG4, 0x00).await?;
+        // Latch INT1 until INT1_SRC is read
+        accel.write_register(CTRL_REG5, 0x08).await?;
+        // Threshold of 16 mg per LSB, 1 sample above the threshold
+        let threshold = (TAMPER_MOTION_MG / 16).clamp(1, 0x7F) as u8;
+        accel.write_register(INT1_THS, threshold).await?;
+        accel.write_register(INT1_DURATION, 0x00).await?;
+        // OR of X/Y/Z high events
+        accel.write_register(INT1_CFG, 0x2A).await?;
+        accel.clear_interrupt().await?;
+
+        info!("Accelerometer initialized");
+        Ok(accel)
+    }
+
+    /// Acceleration on X/Y/Z in mg
+    pub async fn acceleration(&mut self) -> Result<[i16; 3], Error> {
+        let mut buf = [0; 6];
+        self.twim
+            .write_read(ADDRESS, &[OUT_X_L | AUTO_INCREMENT], &mut buf)
+            .await?;
+        // Samples are left justified, the high byte holds the 8 bit low power sample
+        Ok([buf[1], buf[3], buf[5]].map(|b| b as i8 as i16 * MG_PER_LSB))
+    }
+
+    /// Wait for the motion interrupt, then read it with `motion`
+    pub async fn wait_for_motion(&mut self) {
+        self.int.wait_for_high().await
+    }
+
+    /// Clear the latched motion interrupt and return the acceleration after the motion
+    pub async fn motion(&mut self) -> Result<[i16; 3], Error> {
+        self.clear_interrupt().await?;
+        self.acceleration().await
+    }
+
+    async fn clear_interrupt(&mut self) -> Result<(), Error> {
+        self.read_register(INT1_SRC).await?;
+        Ok(())
+    }
+
+    async fn read_register(&mut self, register: u8) -> Result<u8, Error> {
+        let mut buf = [0];
+        self.twim.write_read(ADDRESS, &[register], &mut buf).await?;
+        Ok(buf[0])
+    }
+
+    async fn write_register(&mut self, register: u8, value: u8) -> Result<(), Error> {
+        self.twim.write(ADDRESS, &[register, value]).await?;
+        Ok(())
+    }
+}
+
+/// Classifies motion against the installed orientation, rate limiting the alerts
+pub struct TamperDetector {
+    reference: [i16; 3],
+    last: Option<(TamperKind, Instant)>,
+}
+
+impl TamperDetector {
+    /// `reference` is the acceleration (gravity) with the device installed on the tank
+    pub fn new(reference: [i16; 3]) -> Self {
+        TamperDetector {
+            reference,
+            last: None,
+        }
+    }
+
+    /// Classify a motion event from the acceleration measured after it.  Returns the tamper kind
+    /// to report, unless the same (or a more severe) kind was reported within the holdoff time
+    pub fn update(&mut self, accel: [i16; 3]) -> Option<TamperKind> {
+        let kind = if tilted(self.reference, accel, TAMPER_REMOVED_COS) {
+            TamperKind::Removed
+        } else if tilted(self.reference, accel, TAMPER_TILT_COS) {
+            TamperKind::Tilt
+        } else {
+            TamperKind::Movement
+        };
+
+        let now = Instant::now();
+        if let Some((last, at)) = self.last {
+            if kind <= last && now - at < Duration::from_secs(TAMPER_HOLDOFF_SECS) {
+                return None;
+            }
+        }
+        self.last = Some((kind, now));
+        Some(kind)
+    }
+}
+
+/// Angle between `a` and `b` is larger than acos(`cos`), compared squared to avoid the sqrt
+fn tilted(a: [i16; 3], b: [i16; 3], cos: f32) -> bool {
+    let dot: f32 = a
+        .iter()
+        .zip(b.iter())
+        .map(|(a, b)| *a as f32 * *b as f32)
+        .sum();
+    let norm = |v: [i16; 3]| v.iter().map(|x| *x as f32 * *x as f32).sum::<f32>();
+    dot < 0.0 || dot * dot < cos * cos * norm(a) * norm(b)
+}
@@ -0,0 +1,289 @@
+use crate::config::{TANKS, TANK_COUNT};
+use crate::events::{FaultEvent, LeakAlarm, RefillEvent};
+use crate::level::{ConsumptionStats, LeakDetector, RefillDetector};
+use crate::sensor::{Measurement, SensorFault};
+use crate::TankLevel;
+use defmt::{error, info};
+use heapless::Vec;
+use serde::Serialize;
+
+/// Calibration of a tank level sensor: level = (slope * raw - offset) / 10000, clamped
+#[derive(Debug, Clone, Copy)]
+pub struct SensorProfile {
+    pub slope: u32,
+    pub offset: u32,
+    pub min_level: u32,
+    pub max_level: u32,
+    /// Calibrated raw ADC range, readings outside are not trusted
+    pub cal_min: i16,
+    pub cal_max: i16,
+}
+
+impl SensorProfile {
+    /// Hall effect float gauge
+    pub const HALL_GAUGE: SensorProfile = SensorProfile {
+        slope: 534,
+        offset: 39_0634,
+        min_level: 10,
+        max_level: 100,
+        cal_min: 800,
+        cal_max: 2800,
+    };
+
+    /// Convert sensor ADC value into tank level percentage
+    pub fn level(&self, x: i16) -> u32 {
+        let val = (self.slope * x.max(0) as u32).saturating_sub(self.offset) / 10000;
+        val.clamp(self.min_level, self.max_level)
+    }
+}
+
+/// Scaling of a pressure transducer, the pressure is linear between the raw ADC values at the
+/// transducer's minimum and maximum output.  Current loop (4-20 mA) transducers are read across a
+/// shunt resistor against the internal 0.6 V reference.  Ratiometric transducers are supplied from
+/// the same rail as the SAADC and sampled against VDD/4, so the raw value is the output ratio.
+#[derive(Debug, Clone, Copy)]
+pub struct PressureProfile {
+    /// Raw ADC value at the minimum and maximum output
+    pub raw_min: i16,
+    pub raw_max: i16,
+    /// Pressure (kPa) at the minimum and maximum output
+    pub min_pressure: u32,
+    pub max_pressure: u32,
+    /// Pressure (kPa) of an empty and full tank, for transducers measuring the liquid column.
+    /// `None` reports pressure only, e.g. for vapor pressure
+    pub level: Option<(u32, u32)>,
+    /// Readings at or below are an open circuit, at or above a short
+    pub open_max: i16,
+    pub short_min: i16,
+    /// Time from power up to a valid output (ms)
+    pub settle_ms: u64,
+    /// Sampled against the supply instead of the internal reference
+    pub ratiometric: bool,
+}
+
+impl PressureProfile {
+    /// 4-20 mA, 0-1000 kPa, 150 ohm shunt: 0.6-3.0 V.  Open below 3.6 mA, short above 21 mA
+    pub const CURRENT_LOOP: PressureProfile = PressureProfile {
+        raw_min: 683,
+        raw_max: 3413,
+        min_pressure: 0,
+        max_pressure: 1000,
+        level: None,
+        open_max: 614,
+        short_min: 3584,
+        settle_ms: 50,
+        ratiometric: false,
+    };
+
+    /// Ratiometric 10-90% of the supply, 0-1000 kPa.  Open below 5%, short above 95%
+    pub const RATIOMETRIC: PressureProfile = PressureProfile {
+        raw_min: 410,
+        raw_max: 3686,
+        min_pressure: 0,
+        max_pressure: 1000,
+        level: None,
+        open_max: 205,
+        short_min: 3891,
+        settle_ms: 10,
+        ratiometric: true,
+    };
+
+    /// Convert sensor ADC value into pressure (kPa), clamped to the transducer range
+    pub fn pressure(&self, x: i16) -> u32 {
+        let raw = x.clamp(self.raw_min, self.raw_max) - self.raw_min;
+        let span = self.max_pressure - self.min_pressure;
+        self.min_pressure + raw as u32 * span / (self.raw_max - self.raw_min) as u32
+    }
+
+    /// Convert a pressure (kPa) into tank level percentage, if the profile measures level
+    pub fn level(&self, pressure: u32) -> Option<u32> {
+        self.level.map(|(empty, full)| {
+            let pressure = pressure.clamp(empty, full) - empty;
+            pressure * 100 / (full - empty)
+        })
+    }
+}
+
+/// Shape of a tank, used to convert a liquid height into a level (% of the volume)
+#[derive(Debug, Clone, Copy)]
+pub enum TankGeometry {
+    /// Vertical cylinder or rectangular tank, the level is proportional to the height
+    Vertical { height_mm: u32 },
+    /// Horizontal cylinder, the usual propane tank.  The heads are treated as part of the cylinder
+    Horizontal { diameter_mm: u32 },
+}
+
+impl TankGeometry {
+    /// Height of the tank (mm)
+    pub fn height(&self) -> u32 {
+        match *self {
+            TankGeometry::Vertical { height_mm } => height_mm,
+            TankGeometry::Horizontal { diameter_mm } => diameter_mm,
+        }
+    }
+
+    /// Convert a liquid height (mm) into tank level percentage
+    pub fn level(&self, liquid_mm: u32) -> u32 {
+        let height = self.height();
+        let liquid_mm = liquid_mm.min(height);
+        match *self {
+            TankGeometry::Vertical { .. } => liquid_mm * 100 / height,
+            TankGeometry::Horizontal { .. } => {
+                // Area of the circular segment below the liquid over the area of the circle
+                let x = 1.0 - 2.0 * liquid_mm as f32 / height as f32;
+                let area = libm::acosf(x) - x * libm::sqrtf(1.0 - x * x);
+                libm::roundf(area * 100.0 / core::f32::consts::PI) as u32
+            }
+        }
+    }
+}
+
+/// Ultrasonic sensor mounted below the tank, measuring the liquid height
+#[derive(Debug, Clone, Copy)]
+pub struct UltrasonicProfile {
+    pub geometry: TankGeometry,
+    /// Distance (mm) added to the reading, e.g. for the tank wall.  Negative if the sensor reads
+    /// more than the liquid height
+    pub offset_mm: i32,
+}
+
+impl UltrasonicProfile {
+    /// Convert a distance reading (mm) into tank level percentage
+    pub fn level(&self, distance_mm: i16) -> u32 {
+        let liquid_mm = (distance_mm as i32 + self.offset_mm).max(0) as u32;
+        self.geometry.level(liquid_mm)
+    }
+}
+
+/// Sensor fitted to a tank, with its calibration
+#[derive(Debug, Clone, Copy)]
+pub enum SensorKind {
+    HallEffect(SensorProfile),
+    Pressure(PressureProfile),
+    /// Only one ultrasonic sensor is supported, it needs the `ultrasonic` feature
+    #[cfg(feature = "ultrasonic")]
+    Ultrasonic(UltrasonicProfile),
+}
+
+impl SensorKind {
+    /// Whether the SAADC channel samples against the supply, see `PressureProfile`
+    pub fn ratiometric(&self) -> bool {
+        matches!(self, SensorKind::Pressure(profile) if profile.ratiometric)
+    }
+}
+
+/// A tank connected to one of the board's sensor channels
+#[derive(Debug)]
+pub struct TankConfig {
+    pub id: u8,
+    pub channel: usize,
+    pub sensor: SensorKind,
+}
+
+/// Events raised while monitoring a tank, sent immediately
+#[derive(Debug, Serialize)]
+#[serde(untagged)]
+pub enum TankEvent {
+    Fault(FaultEvent),
+    Refill(RefillEvent),
+    Leak(LeakAlarm),
+}
+
+/// Fault, refill and leak detection for a single tank
+pub struct TankMonitor {
+    /// Id of the monitored tank, the monitors are kept through System OFF so they hold no
+    /// references into the firmware image
+    pub id: u8,
+    stats: Option<ConsumptionStats>,
+    refill: RefillDetector,
+    leak: LeakDetector,
+    last_fault: SensorFault,
+}
+
+impl TankMonitor {
+    pub fn new(config: &TankConfig) -> Self {
+        TankMonitor {
+            id: config.id,
+            stats: None,
+            refill: RefillDetector::new(config.id),
+            leak: LeakDetector::new(config.id),
+            last_fault: SensorFault::None,
+        }
+    }
+
+    /// Process a sensor measurement.  Returns the sample for the payload along with any events to
+    /// send right away.
+    pub fn update(
+        &mut self,
+        measurement: Measurement,
+        battery: u32,
+        timestamp: u32,
+    ) -> (TankLevel, Vec<TankEvent, 3>) {
+        let id = self.id;
+        let Measurement {
+            raw,
+            level,
+            pressure,
+            fault,
+        } = measurement;
+        info!(
+            "Tank {}: {}%, {} kPa, Fault: {}",
+            id, level, pressure, fault
+        );
+
+        let mut events = Vec::new();
+
+        // Report fault status changes so a broken unit is known without waiting for data
+        if fault != self.last_fault {
+            error!(
+                "Tank {} sensor fault status changed: {} -> {}",
+                id, self.last_fault, fault
+            );
+            self.last_fault = fault;
+            let _ = events.push(TankEvent::Fault(FaultEvent::new(id, fault, raw, timestamp)));
+        }
+
+        // Levels from a faulty sensor are meaningless, keep them out of the statistics.  Pressure
+        // only sensors have no level to track
+        if let (false, Some(level)) = (fault.is_fault(), level) {
+            // A sustained level increase is a refill, restart the statistics.  They start at the
+            // first fault free sample
+            let stats = self
+                .stats
+                .get_or_insert_with(|| ConsumptionStats::new(level));
+            if let Some(mut event) = self.refill.update(level, timestamp) {
+                info!(
+                    "Tank {} refill detected: {}% -> {}%",
+                    id, event.before, event.after
+                );
+                event.consumed = stats.consumed();
+                stats.reset(level);
+                self.leak.reset();
+                let _ = events.push(TankEvent::Refill(event));
+            } else {
+                stats.update(level);
+            }
+
+            // An abnormal drop rate is a possible leak
+            if let Some(alarm) = self.leak.update(level, timestamp) {
+                error!(
+                    "Tank {} possible leak: {}%/h, baseline {}%/h",
+                    id, alarm.rate, alarm.baseline
+                );
+                let _ = events.push(TankEvent::Leak(alarm));
+            }
+        }
+
+        // The level of a faulty sensor is not reported either, only the fault
+        let level = if fault.is_fault() { None } else { level };
+        (
+            TankLevel::new(level, pressure, timestamp, battery, fault),
+            events,
+        )
+    }
+}
+
+/// Monitors for all configured tanks, in the order of their SAADC channels
+pub fn tank_monitors() -> Vec<TankMonitor, TANK_COUNT> {
+    TANKS.iter().map(TankMonitor::new).collect()
+}
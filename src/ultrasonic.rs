@@ -0,0 +1,113 @@
+//! Ultrasonic level sensor over UART, enabled with the `ultrasonic` cargo feature.  The sensor
+//! streams frames at 9600 baud, they are parsed by `propane_monitor_core::ultrasonic`, which is
+//! tested on the host.  `receive` keeps the latest distance while the sensor is powered, the
+//! UARTE is only enabled then, and the sensor's `read` takes it.
+use crate::sensor::{Adc, Sensor, SensorFault};
+use crate::tank::UltrasonicProfile;
+use core::cell::Cell;
+use defmt::warn;
+use embassy_futures::select::{select, Either};
+use embassy_nrf::gpio::AnyPin;
+use embassy_nrf::interrupt::UARTE0_SPIM0_SPIS0_TWIM0_TWIS0;
+use embassy_nrf::peripherals::UARTETWISPI0;
+use embassy_nrf::uarte::{self, Baudrate, Parity, UarteRx};
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::blocking_mutex::Mutex;
+use embassy_sync::signal::Signal;
+use embedded_hal::digital::v2::OutputPin;
+use propane_monitor_core::ultrasonic::{check_distance, parse_frame, FRAME_LEN};
+
+/// Frames are sent every 100 ms, two frames worth of bytes always hold a complete frame
+const READ_LEN: usize = 2 * FRAME_LEN;
+
+/// Latest distance (mm) received since the sensor was powered up
+static DISTANCE: Mutex<CriticalSectionRawMutex, Cell<Option<u16>>> = Mutex::new(Cell::new(None));
+/// Set when the sensor is powered up or down, starts and stops `receive`
+static POWERED: Signal<CriticalSectionRawMutex, bool> = Signal::new();
+
+/// UARTE and RX pin for the ultrasonic sensor, the sensor only transmits
+pub struct UltrasonicUart {
+    uarte: UARTETWISPI0,
+    irq: UARTE0_SPIM0_SPIS0_TWIM0_TWIS0,
+    rx: AnyPin,
+}
+
+/// UltrasonicUart constructor
+impl UltrasonicUart {
+    pub fn new(uarte: UARTETWISPI0, irq: UARTE0_SPIM0_SPIS0_TWIM0_TWIS0, rx: AnyPin) -> Self {
+        UltrasonicUart { uarte, irq, rx }
+    }
+}
+
+/// Receive frames while the sensor is powered and keep the latest distance.  Runs forever, spawn
+/// it from a task when an ultrasonic tank is configured
+pub async fn receive(mut uart: UltrasonicUart) -> ! {
+    let mut config = uarte::Config::default();
+    config.baudrate = Baudrate::BAUD9600;
+    config.parity = Parity::EXCLUDED;
+    loop {
+        while !POWERED.wait().await {}
+
+        // The UARTE is disabled again when `rx` is dropped
+        let mut rx = UarteRx::new(&mut uart.uarte, &mut uart.irq, &mut uart.rx, config);
+        let mut buf = [0; READ_LEN];
+        loop {
+            match select(rx.read(&mut buf), POWERED.wait()).await {
+                Either::First(Ok(())) => {
+                    if let Some(distance) = parse_frame(&buf) {
+                        DISTANCE.lock(|latest| latest.set(Some(distance)));
+                    }
+                }
+                Either::First(Err(e)) => warn!("Ultrasonic UART error: {:?}", e),
+                Either::Second(true) => {}
+                Either::Second(false) => break,
+            }
+        }
+    }
+}
+
+/// Ultrasonic level sensor with a switched power pin, read through `receive`
+pub struct UltrasonicSensor<P> {
+    power: P,
+    profile: UltrasonicProfile,
+}
+
+impl<P: OutputPin> UltrasonicSensor<P> {
+    pub fn new(power: P, profile: UltrasonicProfile) -> Self {
+        UltrasonicSensor { power, profile }
+    }
+}
+
+impl<P: OutputPin> Sensor for UltrasonicSensor<P> {
+    fn power_up(&mut self) {
+        DISTANCE.lock(|latest| latest.set(None));
+        let _ = self.power.set_high();
+        POWERED.signal(true);
+    }
+
+    /// The first frame is sent around 500 ms after power up, then every 100 ms.  Leave time for
+    /// `receive` to read two frames worth of bytes
+    fn settle_us(&self) -> u32 {
+        800_000
+    }
+
+    /// The distance (mm), `None` if no frame with a valid checksum was received
+    fn read(&mut self, _adc: &mut dyn Adc) -> Option<i16> {
+        DISTANCE
+            .lock(|latest| latest.take())
+            .map(|distance| distance.min(i16::MAX as u16) as i16)
+    }
+
+    fn power_down(&mut self) {
+        let _ = self.power.set_low();
+        POWERED.signal(false);
+    }
+
+    fn level(&self, raw: i16) -> Option<u32> {
+        Some(self.profile.level(raw))
+    }
+
+    fn check_fault(&mut self, _idle: Option<i16>, raw: i16) -> SensorFault {
+        check_distance(raw, self.profile.offset_mm, self.profile.geometry.height())
+    }
+}